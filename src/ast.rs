@@ -1,72 +1,73 @@
+use vm::{Opcode, Type};
+use std::fmt;
+
 pub struct Procedure {
     pub labels: Vec<(String, usize)>, // label name to operation index
-    pub operations: Vec<Operation>
-}
-
-pub struct Tree {
-    pub procedures: Vec<(String, Procedure)>
-}
-
-pub enum Operation {
-    Jmp(Jmp),
-    Jmps,
-    JmpTrue(JmpTrue),
-    CmpU8,
-    Spi(Spi),
-    Spd(Spd),
-    PushU8(PushU8),
-    PushU64(PushU64),
-    PopU8,
-    SetU8(SetU8),
-    CplU8(CplU8),
-    CpgU8(CpgU8),
-    Halt
-}
-
-pub struct ExtProcRef {
-    pub module: String,
-    pub procedure: String
+    pub operations: Vec<Operation>,
+    pub signature: Option<Signature>
 }
 
-pub struct CpgU8 {
-    pub address: Address
+// proc name(parameters) -> (returns)
+// parameters are pushed in order, so the last parameter is on top of the stack
+pub struct Signature {
+    pub parameters: Vec<Type>,
+    pub returns: Vec<Type>
 }
 
-pub struct CplU8 {
-    pub address: Address
+impl Signature {
+    // compact form as exported in the procedure table, e.g. (u8,u64)->()
+    pub fn to_text(&self) -> String {
+        let parameters: Vec<&str> = self.parameters.iter().map(|t| t.name()).collect();
+        let returns: Vec<&str> = self.returns.iter().map(|t| t.name()).collect();
+        return format!("({})->({})", parameters.join(","), returns.join(","));
+    }
 }
 
-pub struct Jmp {
-    pub address: Address
-}
-
-pub struct JmpTrue {
-    pub address: Address
-}
-
-pub struct PushU64 {
-    pub address: Address
-}
-
-pub struct PushU8 {
-    pub value: u8
-}
-
-pub struct SetU8 {
-    pub address: Address
+pub struct Tree {
+    pub procedures: Vec<(String, Procedure)>,
+    // absolute address the code is loaded at, set with org
+    pub origin: Option<u64>
 }
 
-pub struct Spd {
-    pub value: u64
+// one operand per entry in the operands of the opcode's vm::Instruction
+#[derive(Clone)]
+pub struct Operation {
+    pub opcode: Opcode,
+    pub operands: Vec<Address>
 }
 
-pub struct Spi {
-    pub value: u64
+#[derive(Clone, PartialEq)]
+pub struct ExtProcRef {
+    pub module: String,
+    pub procedure: String
 }
 
+#[derive(Clone, PartialEq)]
 pub enum Address {
     Label(String),
     IntLiteral(u64),
     ProcRef(String),
     ExtProcRef(ExtProcRef)
+}
+
+// operands are written the way the lexer reads them
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Address::Label(label) => write!(f, "&{}", label),
+            Address::IntLiteral(value) => write!(f, "{:#X}", value),
+            Address::ProcRef(procedure) => write!(f, "&this.{}", procedure),
+            Address::ExtProcRef(reference) => write!(f, "&{}.{}", reference.module, reference.procedure)
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.opcode.instruction().mnemonic)?;
+        for operand in &self.operands {
+            write!(f, " {}", operand)?;
+        }
+        return Ok(());
+    }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use byteorder::{WriteBytesExt, BigEndian};
+use byteorder::{ReadBytesExt, WriteBytesExt, BigEndian};
 
 pub fn overwrite_u64(mut at: &mut [u8], value: &u64) {
     at.write_u64::<BigEndian>(*value).expect("Failed to overwrite u64");
@@ -14,4 +14,13 @@ pub fn write_u16(to: &mut Vec<u8>, value: u16) {
 
 pub fn write_u64(to: &mut Vec<u8>, value: u64) {
     to.write_u64::<BigEndian>(value).expect("Failed to write u64");
+}
+
+// writes the lowest `width` bytes of value
+pub fn write_uint(to: &mut Vec<u8>, value: u64, width: usize) {
+    to.write_uint::<BigEndian>(value, width).expect("Failed to write uint");
+}
+
+pub fn read_uint(mut from: &[u8], width: usize) -> u64 {
+    return from.read_uint::<BigEndian>(width).expect("Failed to read uint");
 }
\ No newline at end of file
@@ -0,0 +1,240 @@
+use ast::{Tree, Procedure, Address};
+use error::Warning;
+use vm::Opcode;
+use std::collections::HashMap;
+
+// operations start..end that always run one after the other
+pub struct Block {
+    pub start: usize,
+    pub end: usize,
+    pub successors: Vec<usize>,
+    // control can leave the procedure at the end of this block
+    pub exits: bool
+}
+
+pub struct Graph {
+    pub procedure: String,
+    pub blocks: Vec<Block>
+}
+
+// operation a jump lands on when it stays within the procedure: a label, the start of the procedure itself,
+// or for relative jumps a literal displacement, counted from the end of the jump like the vm does
+pub fn jump_target(name: &str, procedure: &Procedure, labels: &HashMap<&str, usize>, op_index: usize) -> Option<usize> {
+    let operation = &procedure.operations[op_index];
+    match &operation.operands[0] {
+        Address::Label(label) => return labels.get(label.as_str()).copied(),
+        Address::ProcRef(called) if called == name => return Some(0),
+        Address::IntLiteral(value) => match operation.opcode {
+            Opcode::JmpRel8 | Opcode::JmpRel16 | Opcode::JmpTrueRel8 | Opcode::JmpTrueRel16 | Opcode::JmpFalseRel8 | Opcode::JmpFalseRel16 => {
+                let shift = 64 - operation.opcode.instruction().operands[0].width as u32 * 8;
+                let displacement = ((*value << shift) as i64) >> shift;
+                let offsets: Vec<i64> = procedure.operations.iter().scan(0, |offset, operation| {
+                    let start = *offset;
+                    *offset += operation.opcode.instruction().size() as i64;
+                    return Some(start);
+                }).collect();
+                let next = offsets[op_index] + operation.opcode.instruction().size() as i64;
+                return offsets.iter().position(|offset| *offset == next + displacement);
+            },
+            _ => return None
+        },
+        _ => return None
+    }
+}
+
+// jmp, jmp_true, jmp_false, jmps, ret and halt end a block and every label starts one
+// a label whose address is pushed is where a jmp based call returns to, so it is a successor of the push
+fn build(name: &str, procedure: &Procedure) -> Graph {
+    let operations = &procedure.operations;
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    for (label, index) in &procedure.labels {
+        labels.insert(label, *index);
+    }
+
+    let mut leaders: Vec<bool> = vec![false; operations.len() + 1];
+    leaders[0] = true;
+    for (_, index) in &procedure.labels {
+        leaders[*index] = true;
+    }
+    for (op_index, operation) in operations.iter().enumerate() {
+        match operation.opcode {
+            Opcode::Jmp | Opcode::JmpRel8 | Opcode::JmpRel16 |
+            Opcode::JmpTrue | Opcode::JmpTrueRel8 | Opcode::JmpTrueRel16 |
+            Opcode::JmpFalse | Opcode::JmpFalseRel8 | Opcode::JmpFalseRel16 |
+            Opcode::Jmps | Opcode::Ret | Opcode::Halt => leaders[op_index + 1] = true,
+            _ => {}
+        }
+        // a literal displacement lands where no label says so
+        if let Some(Address::IntLiteral(_)) = operation.operands.first() {
+            if let Some(index) = jump_target(name, procedure, &labels, op_index) {
+                leaders[index] = true;
+            }
+        }
+    }
+
+    let mut block_of: Vec<usize> = vec![0; operations.len() + 1];
+    let mut blocks: Vec<Block> = Vec::new();
+    for op_index in 0..operations.len() {
+        if leaders[op_index] {
+            blocks.push(Block{start: op_index, end: op_index, successors: Vec::new(), exits: false});
+        }
+        block_of[op_index] = blocks.len() - 1;
+        blocks.last_mut().unwrap().end = op_index + 1;
+    }
+
+    for block in blocks.iter_mut() {
+        let mut successors: Vec<usize> = Vec::new();
+        let mut falls_through = true;
+        for (op_index, operation) in operations.iter().enumerate().take(block.end).skip(block.start) {
+            match operation.opcode {
+                Opcode::Jmp | Opcode::JmpRel8 | Opcode::JmpRel16 => {
+                    falls_through = false;
+                    match jump_target(name, procedure, &labels, op_index) {
+                        Some(index) => successors.push(index),
+                        None => block.exits = true
+                    }
+                },
+                Opcode::JmpTrue | Opcode::JmpTrueRel8 | Opcode::JmpTrueRel16 | Opcode::JmpFalse | Opcode::JmpFalseRel8 | Opcode::JmpFalseRel16 => {
+                    match jump_target(name, procedure, &labels, op_index) {
+                        Some(index) => successors.push(index),
+                        None => block.exits = true
+                    }
+                },
+                Opcode::Jmps | Opcode::Ret | Opcode::Halt => {
+                    falls_through = false;
+                    block.exits = true;
+                },
+                Opcode::PushU64 => {
+                    if let Address::Label(label) = &operation.operands[0] {
+                        if let Some(index) = labels.get(label.as_str()) {
+                            successors.push(*index);
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+        if falls_through {
+            successors.push(block.end);
+        }
+        // falling off the end of the procedure leaves it as well, but is reported on its own
+        block.successors = successors.iter().filter(|index| **index < operations.len()).map(|index| block_of[*index]).collect();
+        block.successors.dedup();
+    }
+
+    return Graph{procedure: name.to_string(), blocks: blocks};
+}
+
+fn reachable(graph: &Graph, from: usize, seen: &mut [bool]) {
+    let mut pending: Vec<usize> = vec![from];
+    while let Some(block) = pending.pop() {
+        if seen[block] {
+            continue;
+        }
+        seen[block] = true;
+        pending.extend(graph.blocks[block].successors.iter());
+    }
+}
+
+fn check(procedure: &Procedure, graph: &Graph, warnings: &mut Vec<Warning>) {
+    let name = &graph.procedure;
+    let operations = &procedure.operations;
+
+    for (label, _) in &procedure.labels {
+        let used = operations.iter().any(|operation| operation.operands.iter().any(|operand| match operand {
+            Address::Label(used) => used == label,
+            _ => false
+        }));
+        if !used {
+            warnings.push(Warning::UnusedLabel(name.to_string(), label.to_string()));
+        }
+    }
+
+    if graph.blocks.is_empty() {
+        warnings.push(Warning::FallsOffEnd(name.to_string()));
+        return;
+    }
+
+    let mut seen: Vec<bool> = vec![false; graph.blocks.len()];
+    reachable(graph, 0, &mut seen);
+    for (index, block) in graph.blocks.iter().enumerate() {
+        if !seen[index] {
+            warnings.push(Warning::Unreachable(name.to_string(), block.start, block.end - 1));
+        }
+    }
+
+    let last = graph.blocks.last().unwrap();
+    let falls_off = !matches!(operations[last.end - 1].opcode, Opcode::Jmp | Opcode::JmpRel8 | Opcode::JmpRel16 | Opcode::Jmps | Opcode::Ret | Opcode::Halt);
+    if seen[graph.blocks.len() - 1] && falls_off {
+        warnings.push(Warning::FallsOffEnd(name.to_string()));
+    }
+
+    // a reachable block from which no block that leaves the procedure can be reached loops forever
+    let mut exits: Vec<bool> = vec![false; graph.blocks.len()];
+    for (index, block) in graph.blocks.iter().enumerate() {
+        if block.exits || (index == graph.blocks.len() - 1 && falls_off) {
+            exits[index] = true;
+        }
+    }
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (index, block) in graph.blocks.iter().enumerate() {
+            if !exits[index] && block.successors.iter().any(|successor| exits[*successor]) {
+                exits[index] = true;
+                changed = true;
+            }
+        }
+    }
+    let mut reported: Vec<bool> = vec![false; graph.blocks.len()];
+    for (index, block) in graph.blocks.iter().enumerate() {
+        if seen[index] && !exits[index] && !reported[index] {
+            warnings.push(Warning::InfiniteLoop(name.to_string(), block.start));
+            reachable(graph, index, &mut reported);
+        }
+    }
+}
+
+// builds the control flow graph of every procedure and reports unreachable operations, unused labels,
+// procedures that fall off their end and loops without exits
+pub fn analyse(source: &Tree) -> (Vec<Graph>, Vec<Warning>) {
+    let mut graphs: Vec<Graph> = Vec::new();
+    let mut warnings: Vec<Warning> = Vec::new();
+    for (name, procedure) in &source.procedures {
+        let graph = build(name, procedure);
+        check(procedure, &graph, &mut warnings);
+        graphs.push(graph);
+    }
+    return (graphs, warnings);
+}
+
+// graphviz dot with one cluster per procedure and one node per block
+pub fn to_dot(source: &Tree, graphs: &[Graph]) -> String {
+    let mut dot = String::new();
+    dot.push_str("digraph vmw {\n");
+    dot.push_str("    node [shape=box fontname=monospace];\n");
+    for ((_, procedure), graph) in source.procedures.iter().zip(graphs.iter()) {
+        dot.push_str(&format!("    subgraph \"cluster_{}\" {{\n", graph.procedure));
+        dot.push_str(&format!("        label=\"{}\";\n", graph.procedure));
+        for block in &graph.blocks {
+            let mut text = String::new();
+            for (label, index) in &procedure.labels {
+                if *index == block.start {
+                    text.push_str(&format!("{}:\\l", label));
+                }
+            }
+            for op_index in block.start..block.end {
+                text.push_str(&format!("{}\\l", procedure.operations[op_index]));
+            }
+            dot.push_str(&format!("        \"{}.{}\" [label=\"{}\"];\n", graph.procedure, block.start, text));
+        }
+        for block in &graph.blocks {
+            for successor in &block.successors {
+                dot.push_str(&format!("        \"{}.{}\" -> \"{}.{}\";\n", graph.procedure, block.start, graph.procedure, graph.blocks[*successor].start));
+            }
+        }
+        dot.push_str("    }\n");
+    }
+    dot.push_str("}\n");
+    return dot;
+}
\ No newline at end of file
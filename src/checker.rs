@@ -0,0 +1,117 @@
+use ast::{Tree, Procedure, Address, Signature};
+use error::{Error, Warning, AddressKind};
+use vm::{Opcode, Type};
+use std::collections::HashMap;
+use stack;
+
+pub fn address_kind(address: &Address) -> AddressKind {
+    match address {
+        Address::IntLiteral(_) => AddressKind::IntLiteral,
+        Address::Label(_) => AddressKind::Label,
+        Address::ProcRef(_) => AddressKind::ProcRef,
+        Address::ExtProcRef(_) => AddressKind::ExtProcRef
+    }
+}
+
+// semantic checks between parser and generator
+// every operand must be of a kind its instruction accepts and literals must fit the operand width
+pub fn check(source: &Tree) -> Result<(), Vec<Error>> {
+    let mut errors: Vec<Error> = Vec::new();
+
+    for (name, procedure) in &source.procedures {
+        for (op_index, operation) in procedure.operations.iter().enumerate() {
+            let instruction = operation.opcode.instruction();
+            for (operand, address) in instruction.operands.iter().zip(operation.operands.iter()) {
+                let kind = address_kind(address);
+                if !operand.accepts.contains(&kind) {
+                    errors.push(Error::OperandKind(name.to_string(), op_index, instruction.mnemonic, kind));
+                    continue;
+                }
+                match address {
+                    Address::IntLiteral(value) if operand.width < 8 && *value >> (operand.width * 8) != 0 => {
+                        errors.push(Error::OutOfRange(name.to_string(), op_index, instruction.mnemonic, *value));
+                    },
+                    _ => {}
+                }
+            }
+        }
+    }
+    errors.append(&mut stack::analyse(source).2);
+
+    if errors.is_empty() {
+        return Ok(());
+    } else {
+        return Err(errors);
+    }
+}
+
+// type of the value an operation pushes without popping anything
+fn pushed_type(opcode: Opcode) -> Option<Type> {
+    match opcode {
+        Opcode::PushU8 | Opcode::CplU8 | Opcode::CpgU8 => Some(Type::U8),
+        Opcode::PushU16 | Opcode::CplU16 | Opcode::CpgU16 => Some(Type::U16),
+        Opcode::PushU32 | Opcode::CplU32 | Opcode::CpgU32 => Some(Type::U32),
+        Opcode::PushU64 | Opcode::CplU64 | Opcode::CpgU64 => Some(Type::U64),
+        _ => None
+    }
+}
+
+// the arguments of a call are the values pushed directly before it, after the last label,
+// without the return address lower_calls pushes for a call
+fn pushed_arguments(procedure: &Procedure, op_index: usize) -> Vec<Type> {
+    let mut pushed: Vec<Type> = Vec::new();
+    let mut index = op_index;
+    if index > 0 {
+        match (procedure.operations[index - 1].opcode, procedure.operations[index - 1].operands.first()) {
+            (Opcode::PushU64, Some(Address::Label(label))) if label.starts_with("call_return_") => index -= 1,
+            _ => {}
+        }
+    }
+    while index > 0 && !procedure.labels.iter().any(|label| label.1 == index) {
+        match pushed_type(procedure.operations[index - 1].opcode) {
+            Some(t) => pushed.insert(0, t),
+            None => break
+        }
+        index -= 1;
+    }
+    return pushed;
+}
+
+// compares the values pushed before every jmp or call to a procedure of this module with its signature
+pub fn check_calls(source: &Tree) -> Vec<Warning> {
+    let mut warnings: Vec<Warning> = Vec::new();
+    let mut signatures: HashMap<&str, &Signature> = HashMap::new();
+    for (name, procedure) in &source.procedures {
+        if let Some(signature) = &procedure.signature {
+            signatures.insert(name, signature);
+        }
+    }
+
+    for (name, procedure) in &source.procedures {
+        for (op_index, operation) in procedure.operations.iter().enumerate() {
+            if operation.opcode != Opcode::Jmp && operation.opcode != Opcode::Call {
+                continue;
+            }
+            let signature = match &operation.operands[0] {
+                Address::ProcRef(called) => match signatures.get(called.as_str()) {
+                    Some(signature) => (called, signature),
+                    None => continue
+                },
+                _ => continue
+            };
+
+            let pushed = pushed_arguments(procedure, op_index);
+            let parameters = &signature.1.parameters;
+            // only the last pushes are arguments, anything before them belongs to the caller
+            // or is the return address of a jmp based call, and fewer pushes may mean the
+            // remaining arguments were pushed before a label or computed
+            let compared = std::cmp::min(pushed.len(), parameters.len());
+            let mismatch = pushed[pushed.len() - compared..] != parameters[parameters.len() - compared..];
+            if mismatch {
+                let pushed_names: Vec<&str> = pushed.iter().map(|t| t.name()).collect();
+                warnings.push(Warning::Arguments(name.to_string(), op_index, signature.0.to_string(), signature.1.to_text(), pushed_names.join(",")));
+            }
+        }
+    }
+    return warnings;
+}
\ No newline at end of file
@@ -0,0 +1,75 @@
+use vm::Opcode;
+use error::Error;
+use binary::read_uint;
+
+pub struct DecodedOperation {
+    pub offset: u64,
+    pub opcode: Opcode,
+    pub operands: Vec<u64>
+}
+
+// decodes raw code, every operand is read with the width given by the instruction set
+pub fn decode(bin: &[u8]) -> Result<Vec<DecodedOperation>, Error> {
+    let mut operations: Vec<DecodedOperation> = Vec::new();
+    let mut offset: usize = 0;
+    while offset < bin.len() {
+        if offset + 2 > bin.len() {
+            return Err(Error::Truncated(offset as u64));
+        }
+        let value = read_uint(&bin[offset..], 2) as u16;
+        let opcode = match Opcode::from_value(value) {
+            Some(opcode) => opcode,
+            None => return Err(Error::UnknownOpcode(offset as u64, value))
+        };
+        let instruction = opcode.instruction();
+        if offset + instruction.size() as usize > bin.len() {
+            return Err(Error::Truncated(offset as u64));
+        }
+
+        let mut operands: Vec<u64> = Vec::new();
+        let mut operand_offset = offset + 2;
+        for operand in instruction.operands {
+            operands.push(read_uint(&bin[operand_offset..], operand.width));
+            operand_offset += operand.width;
+        }
+        operations.push(DecodedOperation{offset: offset as u64, opcode: opcode, operands: operands});
+        offset = operand_offset;
+    }
+    return Ok(operations);
+}
+
+// one operation per line, prefixed with its offset
+pub fn disassemble(bin: &[u8]) -> Result<String, Error> {
+    return disassemble_at(bin, 0);
+}
+
+// like disassemble for code loaded at base, so the prefix is the absolute address
+pub fn disassemble_at(bin: &[u8], base: u64) -> Result<String, Error> {
+    let mut text = String::new();
+    for operation in decode(bin)? {
+        text.push_str(&format!("{:#010x}: {}", base + operation.offset, operation.opcode.instruction().mnemonic));
+        for operand in &operation.operands {
+            text.push_str(&format!(" {:#x}", operand));
+        }
+        text.push('\n');
+    }
+    return Ok(text);
+}
+
+// like disassemble with the encoded bytes of every operation, names are written as a line of their own before the operation at their offset
+pub fn listing(bin: &[u8], names: &[(u64, String)]) -> Result<String, Error> {
+    let mut text = String::new();
+    for operation in decode(bin)? {
+        for (_, name) in names.iter().filter(|(offset, _)| *offset == operation.offset) {
+            text.push_str(&format!("{}:\n", name));
+        }
+        let size = operation.opcode.instruction().size();
+        let bytes: Vec<String> = bin[operation.offset as usize..(operation.offset + size) as usize].iter().map(|b| format!("{:02x}", b)).collect();
+        text.push_str(&format!("{:#010x}: {:<30} {}", operation.offset, bytes.join(" "), operation.opcode.instruction().mnemonic));
+        for operand in &operation.operands {
+            text.push_str(&format!(" {:#x}", operand));
+        }
+        text.push('\n');
+    }
+    return Ok(text);
+}
\ No newline at end of file
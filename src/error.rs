@@ -0,0 +1,119 @@
+use std::fmt;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum AddressKind {
+    IntLiteral,
+    Label,
+    ProcRef,
+    ExtProcRef
+}
+
+impl fmt::Display for AddressKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddressKind::IntLiteral => write!(f, "integer literal"),
+            AddressKind::Label => write!(f, "label reference"),
+            AddressKind::ProcRef => write!(f, "procedure reference"),
+            AddressKind::ExtProcRef => write!(f, "external procedure reference")
+        }
+    }
+}
+
+pub enum Error {
+    // path, description of what went wrong
+    Io(String, String),
+    // file named by an include, file that includes it
+    Include(String, String),
+    IncludeCycle(String),
+    IncludeDepth(String),
+    // line of the project manifest, what is wrong
+    Manifest(usize, String),
+    // what is wrong with the project manifest as a whole
+    InvalidManifest(String),
+    InvalidToken(String),
+    // number of the procedure in the source, counting from 1
+    InvalidProcedure(usize),
+    // what is wrong with an org directive
+    InvalidOrigin(String),
+    // procedure, operation index, mnemonic, operand kind that was given
+    OperandKind(String, usize, &'static str, AddressKind),
+    // procedure, operation index, mnemonic, literal that does not fit the operand
+    OutOfRange(String, usize, &'static str, u64),
+    // procedure, operation index whose stack height does not fit an i64
+    StackOutOfRange(String, usize),
+    // offset in the binary, value
+    UnknownOpcode(u64, u16),
+    // offset in the binary of the instruction that is cut off
+    Truncated(u64),
+    // what is wrong with the layout of a vmw module
+    InvalidModule(String),
+    // character position in the document, what is wrong
+    Json(usize, String),
+    // line of an intel hex or s-record file or 0 when it is about the whole file, what is wrong
+    InvalidImage(usize, String),
+    // address of the operation the vm stopped at, what went wrong
+    Fault(u64, String),
+    DuplicateLabel(String),
+    UnknownLabel(String, String),
+    UnknownProcedure(String)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(path, error) => write!(f, "{}: {}", path, error),
+            Error::Include(name, including) => write!(f, "{}: could not find include {}", including, name),
+            Error::IncludeCycle(path) => write!(f, "{}: includes itself", path),
+            Error::IncludeDepth(path) => write!(f, "{}: includes are nested too deep", path),
+            Error::Manifest(line, description) => write!(f, "manifest, line {}: {}", line, description),
+            Error::InvalidManifest(description) => write!(f, "manifest: {}", description),
+            Error::InvalidToken(token) => write!(f, "invalid token: {}", token),
+            Error::InvalidProcedure(number) => write!(f, "invalid procedure: procedure {} in the source could not be parsed", number),
+            Error::OperandKind(procedure, operation, mnemonic, kind) => write!(f, "proc {}, operation {}: {} does not accept a {}", procedure, operation, mnemonic, kind),
+            Error::OutOfRange(procedure, operation, mnemonic, value) => write!(f, "proc {}, operation {}: {:#x} does not fit the operand of {}", procedure, operation, value, mnemonic),
+            Error::StackOutOfRange(procedure, operation) => write!(f, "proc {}, operation {}: stack height out of range", procedure, operation),
+            Error::UnknownOpcode(offset, value) => write!(f, "{:#x}: unknown opcode {}", offset, value),
+            Error::Truncated(offset) => write!(f, "{:#x}: instruction is cut off", offset),
+            Error::InvalidModule(description) => write!(f, "invalid module: {}", description),
+            Error::Json(position, description) => write!(f, "invalid json at {}: {}", position, description),
+            Error::InvalidImage(0, description) => write!(f, "invalid image: {}", description),
+            Error::InvalidImage(line, description) => write!(f, "invalid image, line {}: {}", line, description),
+            Error::InvalidOrigin(description) => write!(f, "invalid org: {}", description),
+            Error::Fault(address, description) => write!(f, "{:#x}: {}", address, description),
+            Error::DuplicateLabel(label) => write!(f, "label already used: {}", label),
+            Error::UnknownLabel(procedure, label) => write!(f, "proc {}: could not find label {}", procedure, label),
+            Error::UnknownProcedure(procedure) => write!(f, "could not find proc {}", procedure)
+        }
+    }
+}
+
+
+pub enum Warning {
+    // procedure, operation index, called procedure, its signature, types pushed before the call
+    Arguments(String, usize, String, String, String),
+    // procedure, operation index, stack height on the first path that reached it, height on another path
+    StackHeight(String, usize, i64, i64),
+    // procedure, first operation index reached below the start of the procedure, height there
+    StackUnderflow(String, usize, i64),
+    // procedure, first and last operation index
+    Unreachable(String, usize, usize),
+    // procedure, label
+    UnusedLabel(String, String),
+    FallsOffEnd(String),
+    // procedure, operation index where the loop is entered
+    InfiniteLoop(String, usize)
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::Arguments(procedure, operation, called, signature, pushed) => write!(f, "proc {}, operation {}: {} is declared as {} but the caller pushes ({})", procedure, operation, called, signature, pushed),
+            Warning::StackHeight(procedure, operation, first, other) => write!(f, "proc {}, operation {}: reached with a stack height of {} bytes and of {} bytes", procedure, operation, first, other),
+            Warning::StackUnderflow(procedure, operation, height) => write!(f, "proc {}, operation {}: stack height of {} bytes is below the start of the procedure, it pops what the caller pushed", procedure, operation, height),
+            Warning::Unreachable(procedure, first, last) => write!(f, "proc {}, operations {} to {}: unreachable", procedure, first, last),
+            Warning::UnusedLabel(procedure, label) => write!(f, "proc {}: label {} is never referenced", procedure, label),
+            Warning::FallsOffEnd(procedure) => write!(f, "proc {}: can run past its end without halting or jumping", procedure),
+            Warning::InfiniteLoop(procedure, operation) => write!(f, "proc {}, operation {}: loops forever, no path leaves the procedure", procedure, operation)
+        }
+    }
+}
\ No newline at end of file
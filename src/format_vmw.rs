@@ -0,0 +1,278 @@
+use std::fs::File;
+use std::io::prelude::*;
+use binary::*;
+use error::Error;
+use json;
+use json::Json;
+use map::SymbolMap;
+
+pub struct ExternalProcedure {
+    pub module: String,
+    pub procedure: String
+}
+
+// module layout as described in vmw_format.txt
+#[allow(clippy::upper_case_acronyms)]
+pub struct VMW {
+    pub binary: Vec<u8>,
+    pub procedures: Vec<(String, u64, String)>, // name, offset, signature or empty when not declared
+    pub local_addresses: Vec<u64>,
+    pub external_procedures: Vec<(ExternalProcedure, u64)>
+}
+
+fn write_cstr(to: &mut Vec<u8>, value: &str) {
+    to.extend_from_slice(value.as_bytes());
+    write_u8(to, 0);
+}
+
+// returns: the string, the bytes after its terminating zero
+fn read_cstr(from: &[u8]) -> Result<(String, &[u8]), Error> {
+    match from.iter().position(|b| *b == 0) {
+        Some(end) => match String::from_utf8(from[..end].to_vec()) {
+            Ok(value) => return Ok((value, &from[end+1..])),
+            Err(_) => return Err(Error::InvalidModule("name is not valid utf-8".to_string()))
+        },
+        None => return Err(Error::InvalidModule("name is not terminated".to_string()))
+    }
+}
+
+fn read_u64(from: &[u8]) -> Result<(u64, &[u8]), Error> {
+    if from.len() < 8 {
+        return Err(Error::InvalidModule("section is cut off".to_string()));
+    }
+    return Ok((read_uint(from, 8), &from[8..]));
+}
+
+impl VMW {
+    pub fn new(binary: Vec<u8>, procedures: Vec<(String, u64, String)>, local_addresses: Vec<u64>, external_procedures: Vec<(ExternalProcedure, u64)>) -> VMW {
+        return VMW{binary: binary, procedures: procedures, local_addresses: local_addresses, external_procedures: external_procedures};
+    }
+
+    // raw code for loading at origin, every local address is resolved to an absolute one
+    // and there are no tables, so calls to other modules have to be linked first
+    pub fn to_flat(&self, origin: u64) -> Result<Vec<u8>, Error> {
+        if let Some((external, _)) = self.external_procedures.first() {
+            return Err(Error::UnknownProcedure(format!("{}.{}", external.module, external.procedure)));
+        }
+        let mut binary = self.binary.to_vec();
+        for local_address in &self.local_addresses {
+            let at = *local_address as usize;
+            if at + 8 > binary.len() {
+                return Err(Error::InvalidModule(format!("local address {:#x} is outside of the binary", local_address)));
+            }
+            let value = read_uint(&binary[at..], 8).wrapping_add(origin);
+            overwrite_u64(&mut binary[at..], &value);
+        }
+        return Ok(binary);
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut procedures: Vec<u8> = Vec::new();
+        for (name, offset, _) in &self.procedures {
+            write_cstr(&mut procedures, name);
+            write_u64(&mut procedures, *offset);
+        }
+
+        let mut external_procedures: Vec<u8> = Vec::new();
+        for (external_procedure, offset) in &self.external_procedures {
+            write_cstr(&mut external_procedures, &external_procedure.module);
+            write_cstr(&mut external_procedures, &external_procedure.procedure);
+            write_u64(&mut external_procedures, *offset);
+        }
+
+        let mut local_addresses: Vec<u8> = Vec::new();
+        for local_address in &self.local_addresses {
+            write_u64(&mut local_addresses, *local_address);
+        }
+
+        // only modules that declare signatures have the signature section, so the others keep the original layout
+        let mut signatures: Vec<u8> = Vec::new();
+        if self.procedures.iter().any(|(_, _, signature)| !signature.is_empty()) {
+            for (_, _, signature) in &self.procedures {
+                write_cstr(&mut signatures, signature);
+            }
+        }
+
+        // the index holds the offset of the procedures from the start of the file,
+        // the other sections are relative to the procedures
+        // with signatures the procedures start at 40 instead of 32, readers that follow the index skip the u64 in between,
+        // it holds the offset of the signatures, which come after the binary so the binary still starts where the index says
+        let mut bytes: Vec<u8> = Vec::new();
+        let locals_end = (procedures.len() + external_procedures.len() + local_addresses.len()) as u64;
+        write_u64(&mut bytes, if signatures.is_empty() { 32 } else { 40 });
+        write_u64(&mut bytes, procedures.len() as u64);
+        write_u64(&mut bytes, (procedures.len() + external_procedures.len()) as u64);
+        write_u64(&mut bytes, locals_end);
+        if !signatures.is_empty() {
+            write_u64(&mut bytes, locals_end + self.binary.len() as u64);
+        }
+        bytes.append(&mut procedures);
+        bytes.append(&mut external_procedures);
+        bytes.append(&mut local_addresses);
+        bytes.extend_from_slice(&self.binary);
+        bytes.append(&mut signatures);
+        return bytes;
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<VMW, Error> {
+        let (start, leftover) = read_u64(bytes)?;
+        let (procedures_end, leftover) = read_u64(leftover)?;
+        let (external_procedures_end, leftover) = read_u64(leftover)?;
+        let (local_addresses_end, leftover) = read_u64(leftover)?;
+        let length = bytes.len() as u64;
+        if start < 32 || procedures_end > external_procedures_end || external_procedures_end > local_addresses_end
+            || start.checked_add(local_addresses_end).is_none_or(|end| end > length) {
+            return Err(Error::InvalidModule("index does not match the sections".to_string()));
+        }
+        // procedures that start at 40 or later have the offset of the signatures right after the index,
+        // without it the binary runs to the end of the file
+        let signatures_start = if start >= 40 { read_u64(leftover)?.0 } else { length - start };
+        if signatures_start < local_addresses_end || start.checked_add(signatures_start).is_none_or(|end| end > length) {
+            return Err(Error::InvalidModule("index does not match the sections".to_string()));
+        }
+        let section = |from: u64, to: u64| &bytes[(start + from) as usize..(start + to) as usize];
+
+        let mut procedures: Vec<(String, u64, String)> = Vec::new();
+        let mut leftover = section(0, procedures_end);
+        while !leftover.is_empty() {
+            let (name, rest) = read_cstr(leftover)?;
+            let (offset, rest) = read_u64(rest)?;
+            procedures.push((name, offset, String::new()));
+            leftover = rest;
+        }
+
+        let mut external_procedures: Vec<(ExternalProcedure, u64)> = Vec::new();
+        leftover = section(procedures_end, external_procedures_end);
+        while !leftover.is_empty() {
+            let (module, rest) = read_cstr(leftover)?;
+            let (procedure, rest) = read_cstr(rest)?;
+            let (offset, rest) = read_u64(rest)?;
+            external_procedures.push((ExternalProcedure{module: module, procedure: procedure}, offset));
+            leftover = rest;
+        }
+
+        let mut local_addresses: Vec<u64> = Vec::new();
+        leftover = section(external_procedures_end, local_addresses_end);
+        while !leftover.is_empty() {
+            let (local_address, rest) = read_u64(leftover)?;
+            local_addresses.push(local_address);
+            leftover = rest;
+        }
+
+        let binary = section(local_addresses_end, signatures_start).to_vec();
+
+        leftover = &bytes[(start + signatures_start) as usize..];
+        for procedure in procedures.iter_mut() {
+            if leftover.is_empty() {
+                break;
+            }
+            let (signature, rest) = read_cstr(leftover)?;
+            procedure.2 = signature;
+            leftover = rest;
+        }
+        if !leftover.is_empty() {
+            return Err(Error::InvalidModule("more signatures than procedures".to_string()));
+        }
+
+        return Ok(VMW{binary: binary, procedures: procedures, local_addresses: local_addresses, external_procedures: external_procedures});
+    }
+
+    // the tables of the module as a json document for tools that can not read the binary layout:
+    // {"vmw": 1, "procedures": [{"name", "offset", "signature"}], "externals": [{"module", "procedure", "offset"}],
+    //  "relocations": [local address], "encoding": "hex" or "base64", "code": binary, "symbols": map}
+    // symbols are only written with a map, from_json reads every part back unchanged
+    pub fn to_json(&self, map: Option<&SymbolMap>, base64: bool) -> Json {
+        let mut members = vec![
+            ("vmw", Json::from_u64(1)),
+            ("procedures", Json::Array(self.procedures.iter().map(|(name, offset, signature)| Json::object(vec![
+                ("name", Json::from_text(name)), ("offset", Json::from_u64(*offset)), ("signature", Json::from_text(signature))
+            ])).collect())),
+            ("externals", Json::Array(self.external_procedures.iter().map(|(external, offset)| Json::object(vec![
+                ("module", Json::from_text(&external.module)), ("procedure", Json::from_text(&external.procedure)), ("offset", Json::from_u64(*offset))
+            ])).collect())),
+            ("relocations", Json::Array(self.local_addresses.iter().map(|local_address| Json::from_u64(*local_address)).collect())),
+            ("encoding", Json::from_text(if base64 { "base64" } else { "hex" })),
+            ("code", Json::String(if base64 { json::to_base64(&self.binary) } else { json::to_hex(&self.binary) }))
+        ];
+        if let Some(map) = map {
+            members.push(("symbols", map.to_json()));
+        }
+        return Json::object(members);
+    }
+
+    // returns: the module, the symbol map when the document has one
+    pub fn from_json(document: &Json) -> Result<(VMW, Option<SymbolMap>), Error> {
+        let invalid = |what: &str| Error::InvalidModule(format!("{} is missing or invalid", what));
+        let string = |json: &Json, key: &str| json.get(key).and_then(Json::as_str).map(|value| value.to_string()).ok_or(invalid(key));
+        let number = |json: &Json, key: &str| json.get(key).and_then(Json::as_u64).ok_or(invalid(key));
+        let array = |key: &str| document.get(key).and_then(Json::as_array).ok_or(invalid(key));
+        if document.get("vmw").and_then(Json::as_u64) != Some(1) {
+            return Err(invalid("vmw version"));
+        }
+
+        let mut procedures: Vec<(String, u64, String)> = Vec::new();
+        for procedure in array("procedures")? {
+            procedures.push((string(procedure, "name")?, number(procedure, "offset")?, string(procedure, "signature")?));
+        }
+        let mut external_procedures: Vec<(ExternalProcedure, u64)> = Vec::new();
+        for external in array("externals")? {
+            external_procedures.push((ExternalProcedure{module: string(external, "module")?, procedure: string(external, "procedure")?}, number(external, "offset")?));
+        }
+        let mut local_addresses: Vec<u64> = Vec::new();
+        for local_address in array("relocations")? {
+            local_addresses.push(local_address.as_u64().ok_or(invalid("relocations"))?);
+        }
+        let code = string(document, "code")?;
+        let binary = match document.get("encoding").and_then(Json::as_str) {
+            Some("hex") => json::from_hex(&code),
+            Some("base64") => json::from_base64(&code),
+            _ => return Err(invalid("encoding"))
+        }.ok_or(invalid("code"))?;
+        let map = match document.get("symbols") {
+            Some(symbols) => Some(SymbolMap::from_json(symbols)?),
+            None => None
+        };
+        return Ok((VMW{binary: binary, procedures: procedures, local_addresses: local_addresses, external_procedures: external_procedures}, map));
+    }
+
+    // the size of the code and every table, one entry per line
+    pub fn to_text(&self) -> String {
+        let mut text = format!("binary: {} bytes\n", self.binary.len());
+        text.push_str("procedures:\n");
+        for (name, offset, signature) in &self.procedures {
+            text.push_str(&format!("    {:#018x} {}{}\n", offset, name, signature));
+        }
+        text.push_str("external procedures:\n");
+        for (external, offset) in &self.external_procedures {
+            text.push_str(&format!("    {:#018x} {}.{}\n", offset, external.module, external.procedure));
+        }
+        text.push_str("local addresses:\n");
+        for local_address in &self.local_addresses {
+            text.push_str(&format!("    {:#018x}\n", local_address));
+        }
+        return text;
+    }
+
+    // json documents of to_json or the binary layout
+    // returns: the module, its symbol map when a json document has one
+    pub fn from_contents(contents: &[u8]) -> Result<(VMW, Option<SymbolMap>), Error> {
+        if contents.iter().find(|byte| !(**byte as char).is_whitespace()) == Some(&b'{') {
+            let text = String::from_utf8(contents.to_vec()).map_err(|_| Error::Json(0, "not valid utf-8".to_string()))?;
+            return VMW::from_json(&Json::parse(&text)?);
+        }
+        return VMW::from_bytes(contents).map(|module| (module, None));
+    }
+
+    pub fn from_file(path: &str) -> Result<VMW, Error> {
+        let mut bytes: Vec<u8> = Vec::new();
+        match File::open(path).and_then(|mut f| f.read_to_end(&mut bytes)) {
+            Ok(_) => return VMW::from_bytes(&bytes),
+            Err(error) => return Err(Error::Io(path.to_string(), error.to_string()))
+        }
+    }
+
+    pub fn to_file(&self, path: &str) -> Result<(), Error> {
+        return File::create(path).and_then(|mut f| f.write_all(&self.to_bytes()))
+            .map_err(|error| Error::Io(path.to_string(), error.to_string()));
+    }
+}
\ No newline at end of file
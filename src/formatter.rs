@@ -0,0 +1,175 @@
+use lexer::{Token, lex_line, split_comment, convert_newlines};
+use error::Error;
+
+// operations are indented by this within proc ... end proc
+const INDENT: &str = "    ";
+
+enum Line {
+    Blank,
+    // text at the start of the line, like proc and end proc
+    Outer(String),
+    Label(String),
+    // mnemonic, operands
+    Operation(String, Vec<String>),
+    // a line that only holds a comment, or a comment after the item before it
+    Comment(String),
+    TrailingComment(String)
+}
+
+// integer literals in hex keep being hex, with a lowercase prefix and uppercase digits
+fn operand_text(text: &str, token: &Token) -> String {
+    match token {
+        Token::IntLiteral(value) if text.starts_with("0x") || text.starts_with("0X") => return format!("{:#X}", value),
+        Token::IntLiteral(value) => return value.to_string(),
+        _ => return text.to_string()
+    }
+}
+
+// proc name(u8, u64) -> (u64) or proc name:
+fn proc_text(tokens: &[(&str, Token)]) -> String {
+    let mut text = String::new();
+    for (i, (token_text, token)) in tokens.iter().enumerate() {
+        match token {
+            Token::ParenOpen | Token::ParenClose => text.push_str(token_text),
+            Token::Comma => text.push_str(", "),
+            Token::Arrow => text.push_str(" -> "),
+            _ => {
+                if i > 0 && !text.ends_with('(') && !text.ends_with(' ') {
+                    text.push(' ');
+                }
+                text.push_str(token_text);
+            }
+        }
+    }
+    return text;
+}
+
+// splits a line into the items it holds, every label and operation becomes a line of its own
+fn parse_line(line: &str) -> Result<Vec<Line>, Error> {
+    let (code, comment) = split_comment(line);
+    let mut lines: Vec<Line> = Vec::new();
+    // includes are handled before the lexer
+    if code.trim().starts_with("include ") {
+        lines.push(Line::Outer(code.trim().to_string()));
+        if let Some(comment) = comment {
+            lines.push(Line::TrailingComment(comment.trim_end().to_string()));
+        }
+        return Ok(lines);
+    }
+    let tokens = lex_line(code)?;
+    if tokens.is_empty() {
+        match comment {
+            Some(comment) => lines.push(Line::Comment(comment.trim_end().to_string())),
+            None => lines.push(Line::Blank)
+        }
+        return Ok(lines);
+    }
+
+    match (&tokens[0].1, tokens.get(1).map(|token| &token.1)) {
+        (Token::Proc, _) => lines.push(Line::Outer(proc_text(&tokens))),
+        (Token::Org, Some(address)) if tokens.len() == 2 => lines.push(Line::Outer(format!("org {}", operand_text(tokens[1].0, address)))),
+        (Token::End, Some(Token::Proc)) if tokens.len() == 2 => lines.push(Line::Outer("end proc".to_string())),
+        // lines the parser does not know about stay as they are
+        (Token::Identifier(_), _) => lines.push(Line::Outer(code.trim().to_string())),
+        _ => {
+            let mut i = 0;
+            while i < tokens.len() {
+                match &tokens[i].1 {
+                    Token::Label(label) => lines.push(Line::Label(format!("{}:", label))),
+                    Token::Opcode(opcode) => {
+                        let instruction = opcode.instruction();
+                        let mut operands: Vec<String> = Vec::new();
+                        while operands.len() < instruction.operands.len() && i + 1 < tokens.len() {
+                            match tokens[i + 1].1 {
+                                Token::Opcode(_) | Token::Label(_) => break,
+                                _ => {}
+                            }
+                            i += 1;
+                            operands.push(operand_text(tokens[i].0, &tokens[i].1));
+                        }
+                        lines.push(Line::Operation(instruction.mnemonic.to_string(), operands));
+                    },
+                    _ => lines.push(Line::Operation(tokens[i].0.to_string(), Vec::new()))
+                }
+                i += 1;
+            }
+        }
+    }
+    if let Some(comment) = comment {
+        lines.push(Line::TrailingComment(comment.trim_end().to_string()));
+    }
+    return Ok(lines);
+}
+
+// normalises the layout of a source without changing what it assembles to:
+// operations are indented within their procedure with their operands in one column,
+// labels, proc and end proc start at the beginning of the line, mnemonics are lowercase,
+// hex literals look like 0x1F, runs of blank lines become one and comments stay where they are
+pub fn format(source: &str) -> Result<String, Error> {
+    let mut lines: Vec<Line> = Vec::new();
+    for line in convert_newlines(source).lines() {
+        lines.append(&mut parse_line(line)?);
+    }
+
+    // the mnemonic column of a procedure is as wide as its longest mnemonic
+    let mut widths: Vec<usize> = Vec::new();
+    let mut width = 0;
+    for line in &lines {
+        match line {
+            Line::Outer(text) if text.starts_with("proc") => width = 0,
+            Line::Operation(mnemonic, _) => width = width.max(mnemonic.len()),
+            _ => {}
+        }
+        widths.push(width);
+    }
+    for i in (0..widths.len().saturating_sub(1)).rev() {
+        match &lines[i + 1] {
+            Line::Outer(text) if text.starts_with("proc") => {},
+            _ => widths[i] = widths[i + 1]
+        }
+    }
+
+    let mut text = String::new();
+    let mut in_proc = false;
+    let mut after_proc = false;
+    let mut after_end = false;
+    let mut blank = false;
+    for (line, width) in lines.iter().zip(widths.iter()) {
+        let next = match line {
+            Line::Blank => {
+                blank = true;
+                continue;
+            },
+            Line::TrailingComment(comment) => {
+                text.pop();
+                text.push_str(&format!(" {}\n", comment));
+                continue;
+            },
+            Line::Outer(outer) => {
+                let next = format!("{}\n", outer);
+                if outer.starts_with("proc") {
+                    in_proc = true;
+                } else if outer == "end proc" {
+                    in_proc = false;
+                    // no blank line before end proc
+                    blank = false;
+                }
+                next
+            },
+            Line::Label(label) => format!("{}\n", label),
+            Line::Comment(comment) if in_proc => format!("{}{}\n", INDENT, comment),
+            Line::Comment(comment) => format!("{}\n", comment),
+            Line::Operation(mnemonic, operands) if operands.is_empty() => format!("{}{}\n", INDENT, mnemonic),
+            Line::Operation(mnemonic, operands) => format!("{}{:width$} {}\n", INDENT, mnemonic, operands.join(" "), width = width)
+        };
+        // no blank line at the start of the file or directly after proc, always one between procedures
+        if blank && !text.is_empty() && !after_proc || after_end {
+            text.push('\n');
+        }
+        blank = false;
+        after_proc = next.starts_with("proc");
+        after_end = next == "end proc\n";
+        text.push_str(&next);
+    }
+    return Ok(text);
+}
\ No newline at end of file
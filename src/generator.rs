@@ -1,12 +1,18 @@
 use format_vmw;
-use ast::{Operation, Tree, Address};
-use vm::OpcodeValues;
+use error::Error;
+use checker::address_kind;
+use map::{SymbolMap, Symbol, SymbolKind, Visibility};
+use ast::{Operation, Procedure, Tree, Address};
+use vm::Opcode;
 use std::collections::HashMap;
 use binary::*;
 
 // returns: binary, offsets of procedures, addresses that require program offset, placeholders for external procedure calls
-pub fn generate(source: &Tree) -> format_vmw::VMW {
+// and the symbol map describing where every procedure, label and external reference ended up
+// with short_jumps, jmp, jmp_true and jmp_false to a label use the smallest encoding that reaches it
+pub fn generate(source: &Tree, short_jumps: bool) -> Result<(format_vmw::VMW, SymbolMap), Error> {
     let mut bin: Vec<u8> = Vec::new();
+    let mut map = SymbolMap::new();
     let mut procedures: HashMap<String, u64> = HashMap::new();
     let mut local_addresses: Vec<u64> = Vec::new();
     let mut external_procedures: Vec<(format_vmw::ExternalProcedure, u64)> = Vec::new();
@@ -15,213 +21,214 @@ pub fn generate(source: &Tree) -> format_vmw::VMW {
 
     for (name, procedure) in &source.procedures {
         if procedures.contains_key(name) {
-            panic!("proc label already used");
+            return Err(Error::DuplicateLabel(name.to_string()));
         }
         procedures.insert(name.to_string(), bin.len() as u64);
+        let operations = relax(name, procedure, short_jumps)?;
+        let proc_symbol = map.symbols.len();
+        map.symbols.push(Symbol{kind: SymbolKind::Procedure, name: name.to_string(), offset: bin.len() as u64, size: 0, visibility: Visibility::Global});
+        let mut label_symbols: Vec<usize> = Vec::new();
 
         let mut label_offsets: HashMap<String, u64> = HashMap::new();
         let mut call_placeholders: Vec<(String, u64)> = Vec::new();
         let mut next_label: usize = 0;
-        for (op_index, operation) in procedure.operations.iter().enumerate() {
-            if next_label < procedure.labels.len() && op_index == procedure.labels[next_label].1 {
-                if procedures.contains_key(&procedure.labels[next_label].0) {
-                    panic!("proc label already used");
-                }
-                if label_offsets.contains_key(&procedure.labels[next_label].0) {
-                    panic!("proc label already used");
+        // labels may also point past the last operation, so bind them one more time after it
+        for op_index in 0..(operations.len() + 1) {
+            while next_label < procedure.labels.len() && op_index == procedure.labels[next_label].1 {
+                if procedures.contains_key(&procedure.labels[next_label].0) || label_offsets.contains_key(&procedure.labels[next_label].0) {
+                    return Err(Error::DuplicateLabel(procedure.labels[next_label].0.to_string()));
                 }
                 label_offsets.insert(procedure.labels[next_label].0.to_string(), bin.len() as u64);
+                label_symbols.push(map.symbols.len());
+                map.symbols.push(Symbol{kind: SymbolKind::Label, name: format!("{}.{}", name, procedure.labels[next_label].0), offset: bin.len() as u64, size: 0, visibility: Visibility::Local});
                 next_label += 1;
             }
+            if op_index == operations.len() {
+                break;
+            }
 
             // generate operation
-            let (ref mut add_bin, ref mut add_call_placeholders, ref mut add_proccall_placeholders, ref mut add_extcall_placeholders) = generate_operation(bin.len() as u64, &operation);
+            let (ref mut add_bin, ref mut add_call_placeholders, ref mut add_proccall_placeholders, ref mut add_extcall_placeholders) = generate_operation(bin.len() as u64, &operations[op_index], name, op_index)?;
             call_placeholders.append(add_call_placeholders);
             itern_proc_place.append(add_proccall_placeholders);
+            for (external, offset) in add_extcall_placeholders.iter() {
+                map.symbols.push(Symbol{kind: SymbolKind::External, name: format!("{}.{}", external.module, external.procedure), offset: *offset, size: 8, visibility: Visibility::Import});
+            }
             external_procedures.append(add_extcall_placeholders);
             bin.append(add_bin);
         }
 
+        // a procedure spans until the next one, a label until the next label or the end of its procedure
+        map.symbols[proc_symbol].size = bin.len() as u64 - map.symbols[proc_symbol].offset;
+        for (i, label_symbol) in label_symbols.iter().enumerate() {
+            let end = if i + 1 < label_symbols.len() { map.symbols[label_symbols[i + 1]].offset } else { bin.len() as u64 };
+            map.symbols[*label_symbol].size = end - map.symbols[*label_symbol].offset;
+        }
+
         for call_placeholder in call_placeholders {
-            let maybe_offset = label_offsets.get(&call_placeholder.0);
-            if !maybe_offset.is_some() {
-                println!("{}", &call_placeholder.0);
-                panic!("Could not find label");
-            }
+            let offset = match label_offsets.get(&call_placeholder.0) {
+                Some(offset) => offset,
+                None => return Err(Error::UnknownLabel(name.to_string(), call_placeholder.0))
+            };
             local_addresses.push(call_placeholder.1);
-            overwrite_u64(&mut bin[(call_placeholder.1 as usize)..], maybe_offset.unwrap())
+            overwrite_u64(&mut bin[(call_placeholder.1 as usize)..], offset)
         }
     }
 
     for proccall_placeholder in itern_proc_place {
-        let maybe_offset = procedures.get(&proccall_placeholder.0);
-        if !maybe_offset.is_some() {
-            println!("{}", &proccall_placeholder.0);
-            panic!("Could not find proc");
-        }
+        let offset = match procedures.get(&proccall_placeholder.0) {
+            Some(offset) => offset,
+            None => return Err(Error::UnknownProcedure(proccall_placeholder.0))
+        };
         local_addresses.push(proccall_placeholder.1);
-        overwrite_u64(&mut bin[(proccall_placeholder.1 as usize)..], maybe_offset.unwrap());
+        overwrite_u64(&mut bin[(proccall_placeholder.1 as usize)..], offset);
     }
 
-    let mut procedures_vec: Vec<(String, u64)> = Vec::new();
-    for (key, offset) in procedures {
-        procedures_vec.push((key, offset));
+    // procedure table in source order so the output is byte-identical between runs
+    let mut procedures_vec: Vec<(String, u64, String)> = Vec::new();
+    for (name, procedure) in &source.procedures {
+        let signature = match &procedure.signature {
+            Some(signature) => signature.to_text(),
+            None => String::new()
+        };
+        procedures_vec.push((name.to_string(), procedures[name], signature));
     }
 
     let vmw: format_vmw::VMW = format_vmw::VMW::new(bin, procedures_vec, local_addresses, external_procedures);
-    return vmw;
+    return Ok((vmw, map));
+}
+
+// encodings a jump can be relaxed through, from small to large
+fn jump_encodings(opcode: Opcode) -> Option<[Opcode; 3]> {
+    match opcode {
+        Opcode::Jmp => Some([Opcode::JmpRel8, Opcode::JmpRel16, Opcode::Jmp]),
+        Opcode::JmpTrue => Some([Opcode::JmpTrueRel8, Opcode::JmpTrueRel16, Opcode::JmpTrue]),
+        Opcode::JmpFalse => Some([Opcode::JmpFalseRel8, Opcode::JmpFalseRel16, Opcode::JmpFalse]),
+        _ => None
+    }
 }
 
-// returns: binary, addresses that require placeholders for procedure calls, placeholders for internal procedure calls, placeholders for external procedure calls
-fn generate_operation(bin_offset: u64, operation: &Operation) -> (Vec<u8>, Vec<(String, u64)>, Vec<(String, u64)>, Vec<(format_vmw::ExternalProcedure, u64)>) {
+fn is_relative(opcode: Opcode) -> bool {
+    return matches!(opcode, Opcode::JmpRel8 | Opcode::JmpRel16 | Opcode::JmpTrueRel8 | Opcode::JmpTrueRel16 | Opcode::JmpFalseRel8 | Opcode::JmpFalseRel16);
+}
+
+// resolves labels of relative jumps to displacements and, with short_jumps, picks the smallest encoding for every jump to a label
+// every jump starts at its smallest encoding and only grows, so the label offsets settle after a few rounds
+fn relax(name: &str, procedure: &Procedure, short_jumps: bool) -> Result<Vec<Operation>, Error> {
+    let operations = &procedure.operations;
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    for (label, index) in &procedure.labels {
+        labels.insert(label, *index);
+    }
+    let target = |operation: &Operation| -> Option<usize> {
+        match &operation.operands[0] {
+            Address::Label(label) => labels.get(label.as_str()).copied(),
+            _ => None
+        }
+    };
+
+    // index into jump_encodings for relaxed jumps
+    let mut encodings: Vec<Option<usize>> = operations.iter().map(|operation| {
+        if short_jumps && jump_encodings(operation.opcode).is_some() && target(operation).is_some() { Some(0) } else { None }
+    }).collect();
+    let opcode = |encodings: &Vec<Option<usize>>, op_index: usize| -> Opcode {
+        match encodings[op_index] {
+            Some(encoding) => jump_encodings(operations[op_index].opcode).unwrap()[encoding],
+            None => operations[op_index].opcode
+        }
+    };
+
+    let mut offsets: Vec<i64> = Vec::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        offsets.clear();
+        offsets.push(0);
+        for op_index in 0..operations.len() {
+            let end = offsets[op_index] + opcode(&encodings, op_index).instruction().size() as i64;
+            offsets.push(end);
+        }
+        for op_index in 0..operations.len() {
+            let encoding = match encodings[op_index] {
+                Some(encoding) if encoding < 2 => encoding,
+                _ => continue
+            };
+            let displacement = offsets[target(&operations[op_index]).unwrap()] - offsets[op_index + 1];
+            let width = opcode(&encodings, op_index).instruction().operands[0].width;
+            if !fits(displacement, width) {
+                encodings[op_index] = Some(encoding + 1);
+                changed = true;
+            }
+        }
+    }
+
+    let mut relaxed: Vec<Operation> = Vec::new();
+    for (op_index, operation) in operations.iter().enumerate() {
+        let opcode = opcode(&encodings, op_index);
+        if !is_relative(opcode) {
+            relaxed.push(operation.clone());
+            continue;
+        }
+        let width = opcode.instruction().operands[0].width;
+        let displacement = match &operation.operands[0] {
+            Address::Label(label) => match labels.get(label.as_str()) {
+                Some(index) => offsets[*index] - offsets[op_index + 1],
+                None => return Err(Error::UnknownLabel(name.to_string(), label.to_string()))
+            },
+            Address::IntLiteral(value) => {
+                relaxed.push(Operation{opcode: opcode, operands: vec![Address::IntLiteral(*value)]});
+                continue;
+            },
+            _ => 0
+        };
+        if !fits(displacement, width) {
+            return Err(Error::OutOfRange(name.to_string(), op_index, opcode.instruction().mnemonic, displacement as u64));
+        }
+        let mask = (1u64 << (width * 8)) - 1;
+        relaxed.push(Operation{opcode: opcode, operands: vec![Address::IntLiteral(displacement as u64 & mask)]});
+    }
+    return Ok(relaxed);
+}
+
+// whether a displacement fits a two's complement value of width bytes
+fn fits(displacement: i64, width: usize) -> bool {
+    let bits = width as u32 * 8;
+    return displacement >= -(1i64 << (bits - 1)) && displacement < (1i64 << (bits - 1));
+}
+
+// binary, addresses that require placeholders for procedure calls, placeholders for internal procedure calls, placeholders for external procedure calls
+type GeneratedOperation = (Vec<u8>, Vec<(String, u64)>, Vec<(String, u64)>, Vec<(format_vmw::ExternalProcedure, u64)>);
+
+fn generate_operation(bin_offset: u64, operation: &Operation, proc_name: &str, op_index: usize) -> Result<GeneratedOperation, Error> {
     let mut bin: Vec<u8> = Vec::new();
     let mut call_placeholders: Vec<(String, u64)> = Vec::new();
     let mut proccall_placeholders: Vec<(String, u64)> = Vec::new();
     let mut extcall_placeholders: Vec<(format_vmw::ExternalProcedure, u64)> = Vec::new();
-    
-    match operation {
-        Operation::CmpU8 => {
-            write_u16(&mut bin, OpcodeValues::CmpU8 as u16);
-        },
-        Operation::CpgU8(data) => {
-            write_u16(&mut bin, OpcodeValues::CpgU8 as u16);
-            match &data.address {
-                Address::Label(addr) => {
-                    call_placeholders.push((addr.to_string(), bin_offset+bin.len() as u64));
-                    write_u64(&mut bin, 0);
-                },
-                Address::IntLiteral(addr) => {
-                    write_u64(&mut bin, *addr as u64);
-                },
-                Address::ProcRef(addr) => {
-                    proccall_placeholders.push((addr.to_string(), bin_offset+bin.len() as u64));
-                    write_u64(&mut bin, 0);
-                },
-                Address::ExtProcRef(addr) => {
-                    extcall_placeholders.push((format_vmw::ExternalProcedure{module: addr.module.to_string(), procedure: addr.procedure.to_string()}, bin_offset+bin.len() as u64));
-                    write_u64(&mut bin, 0);
-                }
-            }
-        },
-        Operation::CplU8(data) => {
-            write_u16(&mut bin, OpcodeValues::CplU8 as u16);
-            match &data.address {
-                Address::Label(_) => {
-                    panic!("TODO this should not accept label");
-                },
-                Address::IntLiteral(addr) => {
-                    write_u64(&mut bin, *addr);
-                },
-                Address::ProcRef(_) => {
-                    panic!("TODO this should not accept extlabel");
-                }                
-                Address::ExtProcRef(_) => {
-                    panic!("TODO this should not accept extlabel");
-                }
-            }
-        },
-        Operation::Halt => {
-            write_u16(&mut bin, OpcodeValues::Halt as u16);
-        },
-        Operation::Jmp(data) => {
-            write_u16(&mut bin, OpcodeValues::Jmp as u16);
-            match &data.address {
-                Address::Label(addr) => {
-                    call_placeholders.push((addr.to_string(), bin_offset+bin.len() as u64));
-                    write_u64(&mut bin, 0);
-                },
-                Address::IntLiteral(addr) => {
-                    write_u64(&mut bin, *addr as u64);
-                },
-                Address::ProcRef(addr) => {
-                    proccall_placeholders.push((addr.to_string(), bin_offset+bin.len() as u64));
-                    write_u64(&mut bin, 0);
-                },
-                Address::ExtProcRef(addr) => {
-                    extcall_placeholders.push((format_vmw::ExternalProcedure{module: addr.module.to_string(), procedure: addr.procedure.to_string()}, bin_offset+bin.len() as u64));
-                    write_u64(&mut bin, 0);
-                }
-            }
-        },
-        Operation::Jmps => {
-            write_u16(&mut bin, OpcodeValues::Jmps as u16);
-        },
-        Operation::JmpTrue(data) => {
-            write_u16(&mut bin, OpcodeValues::JmpTrue as u16);
-            match &data.address {
-                Address::Label(addr) => {
-                    call_placeholders.push((addr.to_string(), bin_offset+bin.len() as u64));
-                    write_u64(&mut bin, 0);
-                },
-                Address::IntLiteral(addr) => {
-                    write_u64(&mut bin, *addr as u64);
-                },
-                Address::ProcRef(addr) => {
-                    proccall_placeholders.push((addr.to_string(), bin_offset+bin.len() as u64));
-                    write_u64(&mut bin, 0);
-                },
-                Address::ExtProcRef(addr) => {
-                    extcall_placeholders.push((format_vmw::ExternalProcedure{module: addr.module.to_string(), procedure: addr.procedure.to_string()}, bin_offset+bin.len() as u64));
-                    write_u64(&mut bin, 0);
-                }
-            }            
-        },
-        Operation::PopU8 => {
-            write_u16(&mut bin, OpcodeValues::PopU8 as u16);
-        },
-        Operation::PushU64(data) => {
-            write_u16(&mut bin, OpcodeValues::PushU64 as u16);
-            match &data.address {
-                Address::Label(addr) => {
-                    call_placeholders.push((addr.to_string(), bin_offset+bin.len() as u64));
-                    write_u64(&mut bin, 0);
-                },
-                Address::IntLiteral(addr) => {
-                    write_u64(&mut bin, *addr as u64);
-                },
-                Address::ProcRef(addr) => {
-                    proccall_placeholders.push((addr.to_string(), bin_offset+bin.len() as u64));
-                    write_u64(&mut bin, 0);
-                },
-                Address::ExtProcRef(addr) => {
-                    extcall_placeholders.push((format_vmw::ExternalProcedure{module: addr.module.to_string(), procedure: addr.procedure.to_string()}, bin_offset+bin.len() as u64));
-                    write_u64(&mut bin, 0);
-                }
-            }
-        },
-        Operation::PushU8(data) => {
-            write_u16(&mut bin, OpcodeValues::PushU8 as u16);
-            write_u8(&mut bin, data.value);
-        },
-        Operation::SetU8(data) => {
-            write_u16(&mut bin, OpcodeValues::SetU8 as u16);
-            match &data.address {
-                Address::Label(addr) => {
-                    call_placeholders.push((addr.to_string(), bin_offset+bin.len() as u64));
-                    write_u64(&mut bin, 0);
-                },
-                Address::IntLiteral(addr) => {
-                    write_u64(&mut bin, *addr as u64);
-                },
-                Address::ProcRef(addr) => {
-                    proccall_placeholders.push((addr.to_string(), bin_offset+bin.len() as u64));
-                    write_u64(&mut bin, 0);
-                },
-                Address::ExtProcRef(addr) => {
-                    extcall_placeholders.push((format_vmw::ExternalProcedure{module: addr.module.to_string(), procedure: addr.procedure.to_string()}, bin_offset+bin.len() as u64));
-                    write_u64(&mut bin, 0);
-                }
+
+    let instruction = operation.opcode.instruction();
+    write_u16(&mut bin, operation.opcode as u16);
+    for (operand, address) in instruction.operands.iter().zip(operation.operands.iter()) {
+        if !operand.accepts.contains(&address_kind(address)) {
+            return Err(Error::OperandKind(proc_name.to_string(), op_index, instruction.mnemonic, address_kind(address)));
+        }
+        match address {
+            Address::Label(addr) => {
+                call_placeholders.push((addr.to_string(), bin_offset+bin.len() as u64));
+                write_u64(&mut bin, 0);
+            },
+            Address::IntLiteral(addr) => {
+                write_uint(&mut bin, *addr, operand.width);
+            },
+            Address::ProcRef(addr) => {
+                proccall_placeholders.push((addr.to_string(), bin_offset+bin.len() as u64));
+                write_u64(&mut bin, 0);
+            },
+            Address::ExtProcRef(addr) => {
+                extcall_placeholders.push((format_vmw::ExternalProcedure{module: addr.module.to_string(), procedure: addr.procedure.to_string()}, bin_offset+bin.len() as u64));
+                write_u64(&mut bin, 0);
             }
-        },
-        Operation::Spd(data) => {
-            write_u16(&mut bin, OpcodeValues::Spd as u16);
-            write_u64(&mut bin, data.value);
-        },
-        Operation::Spi(data) => {
-            write_u16(&mut bin, OpcodeValues::Spi as u16);
-            write_u64(&mut bin, data.value);
         }
     }
 
-    return (bin, call_placeholders, proccall_placeholders, extcall_placeholders);
+    return Ok((bin, call_placeholders, proccall_placeholders, extcall_placeholders));
 }
\ No newline at end of file
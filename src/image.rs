@@ -0,0 +1,266 @@
+use error::Error;
+use format_vmw::VMW;
+use map::SymbolMap;
+
+// data bytes per record, like most tools write them
+const RECORD_SIZE: usize = 16;
+// the most bytes between the lowest and highest address of records that are read
+const MAX_SPAN: u64 = 0x100_0000;
+
+// how a module is written, flat, ihex and srec are code resolved for loading at a base address
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Vmw,
+    Json,
+    Flat,
+    IntelHex,
+    SRecord
+}
+
+impl Format {
+    pub const ALL: &'static [Format] = &[Format::Vmw, Format::Json, Format::Flat, Format::IntelHex, Format::SRecord];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Vmw => "vmw",
+            Format::Json => "json",
+            Format::Flat => "flat",
+            Format::IntelHex => "ihex",
+            Format::SRecord => "srec"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Format> {
+        return Format::ALL.iter().find(|format| format.name() == name).copied();
+    }
+}
+
+// how to write a module, from --format, --base and --base64
+#[derive(Clone, Copy)]
+pub struct Image {
+    pub format: Format,
+    // set when it was given, otherwise the origin of the module or 0
+    pub base: Option<u64>,
+    // json code as base64 instead of hex
+    pub base64: bool
+}
+
+impl Image {
+    pub fn new(format: Format) -> Image {
+        return Image{format: format, base: None, base64: false};
+    }
+
+    // map goes into json as debug info, name into the header of s-records
+    pub fn write(&self, module: &VMW, map: Option<&SymbolMap>, origin: Option<u64>, name: &str) -> Result<Vec<u8>, Error> {
+        return to_image(module, map, self.format, self.base.or(origin).unwrap_or(0), self.base64, name);
+    }
+}
+
+// decimal or 0x hex
+pub fn parse_address(text: &str) -> Option<u64> {
+    if text.starts_with("0x") || text.starts_with("0X") {
+        return u64::from_str_radix(&text[2..], 16).ok();
+    }
+    return text.parse::<u64>().ok();
+}
+
+// map goes into json as debug info, name into the header of s-records, base64 writes json code as base64 instead of hex
+pub fn to_image(module: &VMW, map: Option<&SymbolMap>, format: Format, base: u64, base64: bool, name: &str) -> Result<Vec<u8>, Error> {
+    match format {
+        Format::Vmw => return Ok(module.to_bytes()),
+        Format::Json => return Ok(format!("{}\n", module.to_json(map, base64)).into_bytes()),
+        Format::Flat => return module.to_flat(base),
+        Format::IntelHex => return to_intel_hex(&module.to_flat(base)?, base).map(String::into_bytes),
+        Format::SRecord => return to_srecord(&module.to_flat(base)?, base, name).map(String::into_bytes)
+    }
+}
+
+// code of a flat, intel hex or s-record file, flat code starts at base and the others know where they start
+// returns: address of the first byte, code
+pub fn read_code(contents: &[u8], format: Format, base: u64) -> Result<(u64, Vec<u8>), Error> {
+    let text = || String::from_utf8(contents.to_vec()).map_err(|_| Error::InvalidImage(0, "not valid utf-8".to_string()));
+    match format {
+        Format::IntelHex => return from_intel_hex(&text()?),
+        Format::SRecord => return from_srecord(&text()?),
+        _ => return Ok((base, contents.to_vec()))
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    return bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    return bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+}
+
+// returns: bytes of a record written as hex digits
+fn parse_hex_bytes(text: &str, line: usize) -> Result<Vec<u8>, Error> {
+    if !text.len().is_multiple_of(2) || !text.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::InvalidImage(line, "expected pairs of hex digits".to_string()));
+    }
+    let mut bytes: Vec<u8> = Vec::new();
+    for i in (0..text.len()).step_by(2) {
+        bytes.push(u8::from_str_radix(&text[i..i + 2], 16).unwrap());
+    }
+    return Ok(bytes);
+}
+
+// places every (address, data) chunk into one image starting at the lowest address, gaps are zero,
+// records spread further than MAX_SPAN are refused instead of filling the gap
+// returns: base address, image
+fn assemble_chunks(chunks: Vec<(u64, Vec<u8>)>) -> Result<(u64, Vec<u8>), Error> {
+    let base = chunks.iter().map(|(address, _)| *address).min().unwrap_or(0);
+    let end = chunks.iter().map(|(address, data)| address.saturating_add(data.len() as u64)).max().unwrap_or(0);
+    if end - base > MAX_SPAN {
+        return Err(Error::InvalidImage(0, format!("records span {:#x} bytes, images are limited to {:#x}", end - base, MAX_SPAN)));
+    }
+    let mut image: Vec<u8> = vec![0; (end - base) as usize];
+    for (address, data) in chunks {
+        let start = (address - base) as usize;
+        image[start..start + data.len()].copy_from_slice(&data);
+    }
+    return Ok((base, image));
+}
+
+// intel hex with data records and extended linear address records above 64 KiB
+pub fn to_intel_hex(bytes: &[u8], base: u64) -> Result<String, Error> {
+    if base.saturating_add(bytes.len() as u64) > 0x1_0000_0000 {
+        return Err(Error::InvalidImage(0, "intel hex only reaches 4 GiB".to_string()));
+    }
+    let mut text = String::new();
+    let mut upper: Option<u64> = None;
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let address = base + offset as u64;
+        // a record does not cross a 64 KiB boundary
+        let size = RECORD_SIZE.min(bytes.len() - offset).min((0x10000 - (address & 0xFFFF)) as usize);
+        if upper != Some(address >> 16) {
+            upper = Some(address >> 16);
+            text.push_str(&intel_record(0, 4, &[(address >> 24) as u8, (address >> 16) as u8]));
+        }
+        text.push_str(&intel_record(address as u16, 0, &bytes[offset..offset + size]));
+        offset += size;
+    }
+    text.push_str(&intel_record(0, 1, &[]));
+    return Ok(text);
+}
+
+// :LLAAAATT data CC
+fn intel_record(address: u16, kind: u8, data: &[u8]) -> String {
+    let mut record = vec![data.len() as u8, (address >> 8) as u8, address as u8, kind];
+    record.extend_from_slice(data);
+    let sum = checksum(&record);
+    record.push(0u8.wrapping_sub(sum));
+    return format!(":{}\n", hex_bytes(&record));
+}
+
+// returns: address of the first byte, image
+pub fn from_intel_hex(text: &str) -> Result<(u64, Vec<u8>), Error> {
+    let mut chunks: Vec<(u64, Vec<u8>)> = Vec::new();
+    let mut upper: u64 = 0;
+    let mut ended = false;
+    for (i, line) in text.lines().enumerate().map(|(i, line)| (i + 1, line.trim())) {
+        if line.is_empty() {
+            continue;
+        }
+        if ended {
+            return Err(Error::InvalidImage(i, "record after the end of file record".to_string()));
+        }
+        if !line.starts_with(':') {
+            return Err(Error::InvalidImage(i, "record does not start with :".to_string()));
+        }
+        let record = parse_hex_bytes(&line[1..], i)?;
+        if record.len() < 5 || record.len() != record[0] as usize + 5 {
+            return Err(Error::InvalidImage(i, "length does not match the record".to_string()));
+        }
+        if checksum(&record) != 0 {
+            return Err(Error::InvalidImage(i, "checksum does not match".to_string()));
+        }
+        let address = (record[1] as u64) << 8 | record[2] as u64;
+        let data = &record[4..record.len() - 1];
+        match record[3] {
+            0 => chunks.push((upper + address, data.to_vec())),
+            1 => ended = true,
+            // extended segment address, in paragraphs
+            2 if data.len() == 2 => upper = ((data[0] as u64) << 8 | data[1] as u64) << 4,
+            4 if data.len() == 2 => upper = ((data[0] as u64) << 8 | data[1] as u64) << 16,
+            // start addresses do not change the image
+            3 | 5 => {},
+            kind => return Err(Error::InvalidImage(i, format!("unknown record type {:02X}", kind)))
+        }
+    }
+    if !ended {
+        return Err(Error::InvalidImage(0, "end of file record is missing".to_string()));
+    }
+    return assemble_chunks(chunks);
+}
+
+// motorola s-record with a header, the smallest address width that reaches every byte,
+// a record count and a termination record holding base as start address
+pub fn to_srecord(bytes: &[u8], base: u64, header: &str) -> Result<String, Error> {
+    let end = base.saturating_add(bytes.len() as u64);
+    let width = if end <= 0x1_0000 { 2 } else if end <= 0x100_0000 { 3 } else if end <= 0x1_0000_0000 { 4 } else {
+        return Err(Error::InvalidImage(0, "s-records only reach 4 GiB".to_string()));
+    };
+    let mut text = srecord(0, 0, 2, header.as_bytes());
+    let mut count = 0;
+    for (i, chunk) in bytes.chunks(RECORD_SIZE).enumerate() {
+        text.push_str(&srecord(width - 1, base + (i * RECORD_SIZE) as u64, width, chunk));
+        count += 1;
+    }
+    // the count has 16 bits in S5 and 24 bits in S6, beyond that there is none
+    if count <= 0xFFFF {
+        text.push_str(&srecord(5, count, 2, &[]));
+    } else if count <= 0xFF_FFFF {
+        text.push_str(&srecord(6, count, 3, &[]));
+    }
+    text.push_str(&srecord(11 - width, base, width, &[]));
+    return Ok(text);
+}
+
+// Sn count address data checksum, the address is width bytes
+fn srecord(kind: u64, address: u64, width: u64, data: &[u8]) -> String {
+    let mut record = vec![(width as usize + data.len() + 1) as u8];
+    for i in (0..width).rev() {
+        record.push((address >> (i * 8)) as u8);
+    }
+    record.extend_from_slice(data);
+    let sum = checksum(&record);
+    record.push(!sum);
+    return format!("S{}{}\n", kind, hex_bytes(&record));
+}
+
+// returns: address of the first byte, image
+pub fn from_srecord(text: &str) -> Result<(u64, Vec<u8>), Error> {
+    let mut chunks: Vec<(u64, Vec<u8>)> = Vec::new();
+    for (i, line) in text.lines().enumerate().map(|(i, line)| (i + 1, line.trim())) {
+        if line.is_empty() {
+            continue;
+        }
+        if line.len() < 2 || !line.starts_with('S') {
+            return Err(Error::InvalidImage(i, "record does not start with S".to_string()));
+        }
+        let kind = line.as_bytes()[1];
+        let width = match kind {
+            b'0' | b'1' | b'5' | b'9' => 2,
+            b'2' | b'6' | b'8' => 3,
+            b'3' | b'7' => 4,
+            _ => return Err(Error::InvalidImage(i, "unknown record type".to_string()))
+        };
+        let record = parse_hex_bytes(&line[2..], i)?;
+        if record.len() < width + 2 || record.len() != record[0] as usize + 1 {
+            return Err(Error::InvalidImage(i, "length does not match the record".to_string()));
+        }
+        if checksum(&record) != 0xFF {
+            return Err(Error::InvalidImage(i, "checksum does not match".to_string()));
+        }
+        let address = record[1..width + 1].iter().fold(0u64, |address, byte| address << 8 | *byte as u64);
+        match kind {
+            b'1' | b'2' | b'3' => chunks.push((address, record[width + 1..record.len() - 1].to_vec())),
+            // header, count and start address do not change the image
+            _ => {}
+        }
+    }
+    return assemble_chunks(chunks);
+}
\ No newline at end of file
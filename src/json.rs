@@ -0,0 +1,354 @@
+use std::fmt;
+use error::Error;
+
+// a json document, numbers keep their text so u64 values survive a round trip
+#[derive(Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    // members in the order they were written
+    Object(Vec<(String, Json)>)
+}
+
+impl Json {
+    pub fn from_u64(value: u64) -> Json {
+        return Json::Number(value.to_string());
+    }
+
+    pub fn from_text(value: &str) -> Json {
+        return Json::String(value.to_string());
+    }
+
+    pub fn object(members: Vec<(&str, Json)>) -> Json {
+        return Json::Object(members.into_iter().map(|(key, value)| (key.to_string(), value)).collect());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => return members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => return None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => return Some(value),
+            _ => return None
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Json::Number(text) => return text.parse::<u64>().ok(),
+            _ => return None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => return Some(*value),
+            _ => return None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Json>> {
+        match self {
+            Json::Array(items) => return Some(items),
+            _ => return None
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Json, Error> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut parser = Parser{chars: chars, position: 0, depth: 0};
+        let value = parser.value()?;
+        parser.whitespace();
+        if parser.position != parser.chars.len() {
+            return parser.error("unexpected text after the document");
+        }
+        return Ok(value);
+    }
+}
+
+// arrays and objects nested deeper than this are an error instead of running out of stack
+const MAX_DEPTH: usize = 128;
+
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// bytes as lowercase hex digits, two per byte
+pub fn to_hex(bytes: &[u8]) -> String {
+    return bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+}
+
+pub fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    return Some((0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap()).collect());
+}
+
+// standard base64 with padding
+pub fn to_base64(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64[(group >> (18 - i * 6) & 0x3F) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    return text;
+}
+
+pub fn from_base64(text: &str) -> Option<Vec<u8>> {
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut bytes: Vec<u8> = Vec::new();
+    for (i, chunk) in text.chunks(4).enumerate() {
+        let padding = chunk.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 || padding > 0 && i + 1 != text.len() / 4 {
+            return None;
+        }
+        let mut group: u32 = 0;
+        for c in &chunk[..4 - padding] {
+            group = group << 6 | BASE64.iter().position(|b| b == c)? as u32;
+        }
+        group <<= 6 * padding as u32;
+        for i in 0..(3 - padding) {
+            bytes.push((group >> (16 - i * 8)) as u8);
+        }
+    }
+    return Some(bytes);
+}
+
+fn write_string(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?
+        }
+    }
+    return write!(f, "\"");
+}
+
+// compact, without any whitespace
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(text) => write!(f, "{}", text),
+            Json::String(value) => write_string(f, value),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            },
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    // arrays and objects the parser is in
+    depth: usize
+}
+
+impl Parser {
+    fn error<T>(&self, description: &str) -> Result<T, Error> {
+        return Err(Error::Json(self.position, description.to_string()));
+    }
+
+    fn whitespace(&mut self) {
+        while self.position < self.chars.len() && self.chars[self.position].is_whitespace() {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        return self.chars.get(self.position).copied();
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), Error> {
+        for c in word.chars() {
+            if self.peek() != Some(c) {
+                return self.error(&format!("expected {}", word));
+            }
+            self.position += 1;
+        }
+        return Ok(());
+    }
+
+    fn value(&mut self) -> Result<Json, Error> {
+        self.whitespace();
+        match self.peek() {
+            Some('n') => { self.expect("null")?; return Ok(Json::Null); },
+            Some('t') => { self.expect("true")?; return Ok(Json::Bool(true)); },
+            Some('f') => { self.expect("false")?; return Ok(Json::Bool(false)); },
+            Some('"') => return Ok(Json::String(self.string()?)),
+            Some('[') | Some('{') => {
+                if self.depth == MAX_DEPTH {
+                    return self.error(&format!("arrays and objects are nested deeper than {}", MAX_DEPTH));
+                }
+                self.depth += 1;
+                let value = if self.peek() == Some('[') { self.array() } else { self.object() };
+                self.depth -= 1;
+                return value;
+            },
+            Some(c) if c == '-' || c.is_ascii_digit() => return self.number(),
+            _ => return self.error("expected a value")
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, Error> {
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E' {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        if text.parse::<f64>().is_err() {
+            return self.error(&format!("invalid number {}", text));
+        }
+        return Ok(Json::Number(text));
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        self.expect("\"")?;
+        let mut value = String::new();
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return self.error("string is not terminated")
+            };
+            self.position += 1;
+            match c {
+                '"' => return Ok(value),
+                '\\' => {
+                    let escaped = match self.peek() {
+                        Some(escaped) => escaped,
+                        None => return self.error("string is not terminated")
+                    };
+                    self.position += 1;
+                    match escaped {
+                        '"' | '\\' | '/' => value.push(escaped),
+                        'n' => value.push('\n'),
+                        'r' => value.push('\r'),
+                        't' => value.push('\t'),
+                        'b' => value.push('\u{8}'),
+                        'f' => value.push('\u{c}'),
+                        'u' => value.push(self.unicode()?),
+                        _ => return self.error("invalid escape")
+                    }
+                },
+                c => value.push(c)
+            }
+        }
+    }
+
+    // \uXXXX, including surrogate pairs
+    fn unicode(&mut self) -> Result<char, Error> {
+        let high = self.hex4()?;
+        if (0xD800..0xDC00).contains(&high) {
+            self.expect("\\u")?;
+            let low = self.hex4()?;
+            let code = 0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+            return Ok(::std::char::from_u32(code).unwrap_or('\u{FFFD}'));
+        }
+        return Ok(::std::char::from_u32(high).unwrap_or('\u{FFFD}'));
+    }
+
+    fn hex4(&mut self) -> Result<u32, Error> {
+        if self.position + 4 > self.chars.len() {
+            return self.error("escape is cut off");
+        }
+        let text: String = self.chars[self.position..self.position + 4].iter().collect();
+        self.position += 4;
+        match u32::from_str_radix(&text, 16) {
+            Ok(value) => return Ok(value),
+            Err(_) => return self.error("invalid escape")
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, Error> {
+        self.expect("[")?;
+        let mut items: Vec<Json> = Vec::new();
+        self.whitespace();
+        if self.peek() == Some(']') {
+            self.position += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(']') => {
+                    self.position += 1;
+                    return Ok(Json::Array(items));
+                },
+                _ => return self.error("expected , or ]")
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, Error> {
+        self.expect("{")?;
+        let mut members: Vec<(String, Json)> = Vec::new();
+        self.whitespace();
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.whitespace();
+            let key = self.string()?;
+            self.whitespace();
+            self.expect(":")?;
+            members.push((key, self.value()?));
+            self.whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some('}') => {
+                    self.position += 1;
+                    return Ok(Json::Object(members));
+                },
+                _ => return self.error("expected , or }")
+            }
+        }
+    }
+}
\ No newline at end of file
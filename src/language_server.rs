@@ -0,0 +1,454 @@
+use std::io::prelude::*;
+use std::io::BufRead;
+use json::Json;
+use lexer::{Token, lex_line, split_comment, convert_newlines};
+use error::{Error, Warning};
+use map::SymbolKind;
+use vm::{Opcode, INSTRUCTIONS};
+use {Options, assemble_named};
+
+// a language server protocol server over stdio, started by the lsp command
+// documents are synchronised in full, positions count characters, which is the same as utf-16 for ascii sources
+
+// line, first and one past the last character
+#[derive(Clone, Copy)]
+struct Span {
+    line: usize,
+    start: usize,
+    end: usize
+}
+
+enum Symbol {
+    Mnemonic(Opcode),
+    LabelRef(String),
+    ProcRef(String),
+    Label(String),
+    ProcName(String),
+    Other
+}
+
+// where the procedures, labels and operations of a document are
+struct Outline {
+    // name, span of the name, line of proc and of end proc
+    procedures: Vec<(String, Span, usize, usize)>,
+    // procedure, label, span
+    labels: Vec<(String, String, Span)>,
+    // procedure, operation index, span of the mnemonic
+    operations: Vec<(String, usize, Span)>,
+    // procedure or empty outside of procedures, what the token is, span
+    tokens: Vec<(String, Symbol, Span)>
+}
+
+impl Outline {
+    fn new(text: &str) -> Outline {
+        let mut outline = Outline{procedures: Vec::new(), labels: Vec::new(), operations: Vec::new(), tokens: Vec::new()};
+        let mut procedure = String::new();
+        let mut op_index = 0;
+        for (line_index, line) in convert_newlines(text).lines().enumerate() {
+            let code = split_comment(line).0;
+            // lines that do not lex, like includes, have nothing to show
+            let tokens = match lex_line(code) {
+                Ok(tokens) => tokens,
+                Err(_) => continue
+            };
+            for (i, (token_text, token)) in tokens.iter().enumerate() {
+                let byte = token_text.as_ptr() as usize - code.as_ptr() as usize;
+                let start = code[..byte].chars().count();
+                let span = Span{line: line_index, start: start, end: start + token_text.chars().count()};
+                let symbol = match token {
+                    Token::Proc if i == 0 => {
+                        match tokens.get(1).map(|next| &next.1) {
+                            Some(Token::Label(name)) | Some(Token::Identifier(name)) => {
+                                procedure = name.to_string();
+                                op_index = 0;
+                            },
+                            _ => {}
+                        }
+                        Symbol::Other
+                    },
+                    Token::Label(name) | Token::Identifier(name) if i == 1 && matches!(tokens[0].1, Token::Proc) => {
+                        let name_span = Span{line: span.line, start: span.start, end: span.start + name.len()};
+                        outline.procedures.push((name.to_string(), name_span, line_index, line_index));
+                        Symbol::ProcName(name.to_string())
+                    },
+                    Token::End => {
+                        if let Some(last) = outline.procedures.last_mut() {
+                            last.3 = line_index;
+                        }
+                        Symbol::Other
+                    },
+                    Token::Label(label) => {
+                        outline.labels.push((procedure.to_string(), label.to_string(), span));
+                        Symbol::Label(label.to_string())
+                    },
+                    Token::Opcode(opcode) => {
+                        outline.operations.push((procedure.to_string(), op_index, span));
+                        op_index += 1;
+                        Symbol::Mnemonic(*opcode)
+                    },
+                    Token::LabelRef(label) => Symbol::LabelRef(label.to_string()),
+                    Token::ProcRef(name) => Symbol::ProcRef(name.to_string()),
+                    _ => Symbol::Other
+                };
+                outline.tokens.push((procedure.to_string(), symbol, span));
+            }
+        }
+        return outline;
+    }
+
+    fn token_at(&self, line: usize, character: usize) -> Option<&(String, Symbol, Span)> {
+        return self.tokens.iter().find(|(_, _, span)| span.line == line && span.start <= character && character <= span.end);
+    }
+
+    fn procedure_at(&self, line: usize) -> Option<&str> {
+        return self.procedures.iter().find(|(_, _, first, last)| *first <= line && line <= *last).map(|(name, _, _, _)| name.as_str());
+    }
+
+    fn procedure(&self, name: &str) -> Option<Span> {
+        return self.procedures.iter().find(|(procedure, _, _, _)| procedure == name).map(|(_, span, _, _)| *span);
+    }
+
+    fn label(&self, procedure: &str, label: &str) -> Option<Span> {
+        return self.labels.iter().find(|(in_procedure, name, _)| in_procedure == procedure && name == label).map(|(_, _, span)| *span);
+    }
+
+    fn operation(&self, procedure: &str, op_index: usize) -> Option<Span> {
+        return self.operations.iter().find(|(in_procedure, index, _)| in_procedure == procedure && *index == op_index).map(|(_, _, span)| *span);
+    }
+
+    // the first reference to a label within a procedure
+    fn label_ref(&self, procedure: &str, label: &str) -> Option<Span> {
+        return self.tokens.iter().find(|(in_procedure, symbol, _)| in_procedure == procedure && match symbol {
+            Symbol::LabelRef(name) => name == label,
+            _ => false
+        }).map(|(_, _, span)| *span);
+    }
+
+    // where an error is reported, the start of the document when that is not known
+    fn error_span(&self, error: &Error, text: &str) -> Option<Span> {
+        match error {
+            Error::OperandKind(procedure, op_index, _, _) | Error::OutOfRange(procedure, op_index, _, _) |
+            Error::StackOutOfRange(procedure, op_index) => return self.operation(procedure, *op_index),
+            Error::UnknownLabel(procedure, label) => return self.label_ref(procedure, label),
+            Error::DuplicateLabel(label) => return self.labels.iter().filter(|(_, name, _)| name == label).nth(1).map(|(_, _, span)| *span).or(self.procedure(label)),
+            Error::UnknownProcedure(name) => return self.tokens.iter().find(|(_, symbol, _)| match symbol { Symbol::ProcRef(procedure) => procedure == name, _ => false }).map(|(_, _, span)| *span),
+            Error::InvalidProcedure(number) => return self.procedures.get(number - 1).map(|(_, span, _, _)| *span),
+            Error::InvalidToken(token) => {
+                for (line_index, line) in convert_newlines(text).lines().enumerate() {
+                    if let Some(byte) = split_comment(line).0.find(token.as_str()) {
+                        let start = line[..byte].chars().count();
+                        return Some(Span{line: line_index, start: start, end: start + token.chars().count()});
+                    }
+                }
+                return None;
+            },
+            _ => return None
+        }
+    }
+
+    fn warning_span(&self, warning: &Warning) -> Option<Span> {
+        match warning {
+            Warning::Arguments(procedure, op_index, _, _, _) | Warning::StackHeight(procedure, op_index, _, _) |
+            Warning::StackUnderflow(procedure, op_index, _) | Warning::InfiniteLoop(procedure, op_index) => return self.operation(procedure, *op_index),
+            Warning::Unreachable(procedure, first, last) => {
+                let first = self.operation(procedure, *first)?;
+                let last = self.operation(procedure, *last).unwrap_or(first);
+                // spans a single line, so only the first operation is marked when they are on different lines
+                return Some(if first.line == last.line { Span{line: first.line, start: first.start, end: last.end} } else { first });
+            },
+            Warning::UnusedLabel(procedure, label) => return self.label(procedure, label),
+            Warning::FallsOffEnd(procedure) => return self.procedure(procedure)
+        }
+    }
+}
+
+fn range(span: Span) -> Json {
+    let position = |character: usize| Json::object(vec![("line", Json::from_u64(span.line as u64)), ("character", Json::from_u64(character as u64))]);
+    return Json::object(vec![("start", position(span.start)), ("end", position(span.end))]);
+}
+
+fn location(uri: &str, span: Span) -> Json {
+    return Json::object(vec![("uri", Json::from_text(uri)), ("range", range(span))]);
+}
+
+// file:///some/path.asm to /some/path.asm, includes are resolved relative to it
+fn uri_to_path(uri: &str) -> String {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let mut decoded: Vec<u8> = Vec::new();
+    let bytes = path.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Ok(byte) = u8::from_str_radix(&path[i+1..i+3], 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    return String::from_utf8_lossy(&decoded).to_string();
+}
+
+fn describe_instruction(opcode: Opcode) -> String {
+    let instruction = opcode.instruction();
+    let operands: Vec<String> = instruction.operands.iter().map(|operand| format!("{} bytes", operand.width)).collect();
+    return format!("**{}**\n\nopcode {} ({:#06x}), {} bytes encoded, operands: {}\n\npops {} bytes, pushes {} bytes",
+        instruction.mnemonic, opcode as u16, opcode as u16, instruction.size(),
+        if operands.is_empty() { "none".to_string() } else { operands.join(", ") },
+        instruction.pops, instruction.pushes);
+}
+
+#[derive(Default)]
+pub struct Server {
+    // uri, text
+    documents: Vec<(String, String)>,
+    shutdown: bool
+}
+
+impl Server {
+    pub fn new() -> Server {
+        return Server{documents: Vec::new(), shutdown: false};
+    }
+
+    fn document(&self, uri: &str) -> Option<&str> {
+        return self.documents.iter().find(|(document, _)| document == uri).map(|(_, text)| text.as_str());
+    }
+
+    fn set_document(&mut self, uri: &str, text: &str) {
+        self.documents.retain(|(document, _)| document != uri);
+        self.documents.push((uri.to_string(), text.to_string()));
+    }
+
+    fn diagnostics(&self, uri: &str) -> Json {
+        let mut diagnostics: Vec<Json> = Vec::new();
+        if let Some(text) = self.document(uri) {
+            let outline = Outline::new(text);
+            let start = Span{line: 0, start: 0, end: 0};
+            let diagnostic = |span: Span, severity: u64, message: String| Json::object(vec![
+                ("range", range(span)), ("severity", Json::from_u64(severity)), ("source", Json::from_text("vmw")), ("message", Json::String(message))
+            ]);
+            let (errors, warnings) = match assemble_named(text, &uri_to_path(uri), &Options::new()) {
+                Ok(assembly) => (Vec::new(), assembly.warnings),
+                Err(diagnostics) => (diagnostics.errors, diagnostics.warnings)
+            };
+            for error in &errors {
+                diagnostics.push(diagnostic(outline.error_span(error, text).unwrap_or(start), 1, error.to_string()));
+            }
+            for warning in &warnings {
+                diagnostics.push(diagnostic(outline.warning_span(warning).unwrap_or(start), 2, warning.to_string()));
+            }
+        }
+        return Json::object(vec![
+            ("jsonrpc", Json::from_text("2.0")),
+            ("method", Json::from_text("textDocument/publishDiagnostics")),
+            ("params", Json::object(vec![("uri", Json::from_text(uri)), ("diagnostics", Json::Array(diagnostics))]))
+        ]);
+    }
+
+    fn definition(&self, uri: &str, text: &str, line: usize, character: usize) -> Json {
+        let outline = Outline::new(text);
+        let span = match outline.token_at(line, character) {
+            Some((procedure, Symbol::LabelRef(label), _)) => outline.label(procedure, label),
+            Some((_, Symbol::ProcRef(name), _)) => outline.procedure(name),
+            _ => None
+        };
+        match span {
+            Some(span) => return location(uri, span),
+            None => return Json::Null
+        }
+    }
+
+    fn hover(&self, uri: &str, text: &str, line: usize, character: usize) -> Json {
+        let outline = Outline::new(text);
+        let (procedure, symbol, span) = match outline.token_at(line, character) {
+            Some(token) => token,
+            None => return Json::Null
+        };
+        // offsets are only known when the document assembles
+        let map = assemble_named(text, &uri_to_path(uri), &Options::new()).ok().map(|assembly| assembly.map);
+        let offset = |name: &str, kind: SymbolKind| -> String {
+            let symbol = map.as_ref().and_then(|map| map.symbols.iter().find(|symbol| symbol.name == name &&
+                matches!((&symbol.kind, &kind), (SymbolKind::Procedure, SymbolKind::Procedure) | (SymbolKind::Label, SymbolKind::Label))));
+            match symbol {
+                Some(symbol) => return format!("\n\noffset {:#x}, {} bytes", symbol.offset, symbol.size),
+                None => return String::new()
+            }
+        };
+        let contents = match symbol {
+            Symbol::Mnemonic(opcode) => describe_instruction(*opcode),
+            Symbol::ProcName(name) | Symbol::ProcRef(name) => format!("**proc {}**{}", name, offset(name, SymbolKind::Procedure)),
+            Symbol::Label(label) | Symbol::LabelRef(label) => format!("**{}** in proc {}{}", label, procedure, offset(&format!("{}.{}", procedure, label), SymbolKind::Label)),
+            Symbol::Other => return Json::Null
+        };
+        return Json::object(vec![
+            ("contents", Json::object(vec![("kind", Json::from_text("markdown")), ("value", Json::String(contents))])),
+            ("range", range(*span))
+        ]);
+    }
+
+    fn completion(&self, text: &str, line: usize) -> Json {
+        let outline = Outline::new(text);
+        let mut items: Vec<Json> = Vec::new();
+        let item = |label: String, kind: u64, detail: String| Json::object(vec![("label", Json::String(label)), ("kind", Json::from_u64(kind)), ("detail", Json::String(detail))]);
+        for instruction in INSTRUCTIONS {
+            items.push(item(instruction.mnemonic.to_string(), 14, format!("opcode {}", instruction.opcode as u16)));
+        }
+        if let Some(procedure) = outline.procedure_at(line) {
+            for (_, label, _) in outline.labels.iter().filter(|(in_procedure, _, _)| in_procedure == procedure) {
+                items.push(item(format!("&{}", label), 18, format!("label in proc {}", procedure)));
+            }
+        }
+        for (name, _, _, _) in &outline.procedures {
+            items.push(item(format!("&this.{}", name), 3, "procedure".to_string()));
+        }
+        return Json::Array(items);
+    }
+
+    fn document_symbols(&self, text: &str) -> Json {
+        let outline = Outline::new(text);
+        let mut symbols: Vec<Json> = Vec::new();
+        for (name, span, first, last) in &outline.procedures {
+            let children: Vec<Json> = outline.labels.iter().filter(|(procedure, _, _)| procedure == name).map(|(_, label, label_span)| Json::object(vec![
+                ("name", Json::from_text(label)), ("kind", Json::from_u64(14)), ("range", range(*label_span)), ("selectionRange", range(*label_span))
+            ])).collect();
+            let whole = Json::object(vec![
+                ("start", Json::object(vec![("line", Json::from_u64(*first as u64)), ("character", Json::from_u64(0))])),
+                ("end", Json::object(vec![("line", Json::from_u64(*last as u64)), ("character", Json::from_u64(8))]))
+            ]);
+            symbols.push(Json::object(vec![
+                ("name", Json::from_text(name)), ("kind", Json::from_u64(12)), ("range", whole), ("selectionRange", range(*span)), ("children", Json::Array(children))
+            ]));
+        }
+        return Json::Array(symbols);
+    }
+
+    // returns: the result or error of a request, notifications to send
+    fn handle(&mut self, method: &str, params: &Json) -> (Result<Json, (i64, String)>, Vec<Json>) {
+        let document = params.get("textDocument");
+        let uri = document.and_then(|document| document.get("uri")).and_then(|uri| uri.as_str()).unwrap_or("").to_string();
+        let position = params.get("position");
+        let line = position.and_then(|position| position.get("line")).and_then(|line| line.as_u64()).unwrap_or(0) as usize;
+        let character = position.and_then(|position| position.get("character")).and_then(|character| character.as_u64()).unwrap_or(0) as usize;
+        let text = self.document(&uri).unwrap_or("").to_string();
+
+        match method {
+            "initialize" => return (Ok(Json::object(vec![
+                ("capabilities", Json::object(vec![
+                    ("textDocumentSync", Json::object(vec![("openClose", Json::Bool(true)), ("change", Json::from_u64(1)), ("save", Json::object(vec![("includeText", Json::Bool(true))]))])),
+                    ("definitionProvider", Json::Bool(true)),
+                    ("hoverProvider", Json::Bool(true)),
+                    ("completionProvider", Json::object(vec![("triggerCharacters", Json::Array(vec![Json::from_text("&"), Json::from_text(".")]))])),
+                    ("documentSymbolProvider", Json::Bool(true))
+                ])),
+                ("serverInfo", Json::object(vec![("name", Json::from_text("vmw_assembler"))]))
+            ])), Vec::new()),
+            "shutdown" => {
+                self.shutdown = true;
+                return (Ok(Json::Null), Vec::new());
+            },
+            "textDocument/didOpen" => {
+                let text = document.and_then(|document| document.get("text")).and_then(|text| text.as_str()).unwrap_or("");
+                self.set_document(&uri, text);
+                return (Ok(Json::Null), vec![self.diagnostics(&uri)]);
+            },
+            // full synchronisation, the last change holds the whole document
+            "textDocument/didChange" => {
+                let changes = params.get("contentChanges").and_then(|changes| changes.as_array());
+                if let Some(text) = changes.and_then(|changes| changes.last()).and_then(|change| change.get("text")).and_then(|text| text.as_str()) {
+                    self.set_document(&uri, text);
+                }
+                return (Ok(Json::Null), Vec::new());
+            },
+            "textDocument/didSave" => {
+                if let Some(text) = params.get("text").and_then(|text| text.as_str()) {
+                    self.set_document(&uri, text);
+                }
+                return (Ok(Json::Null), vec![self.diagnostics(&uri)]);
+            },
+            "textDocument/didClose" => {
+                self.documents.retain(|(document, _)| *document != uri);
+                return (Ok(Json::Null), vec![self.diagnostics(&uri)]);
+            },
+            "textDocument/definition" => return (Ok(self.definition(&uri, &text, line, character)), Vec::new()),
+            "textDocument/hover" => return (Ok(self.hover(&uri, &text, line, character)), Vec::new()),
+            "textDocument/completion" => return (Ok(self.completion(&text, line)), Vec::new()),
+            "textDocument/documentSymbol" => return (Ok(self.document_symbols(&text)), Vec::new()),
+            _ => return (Err((-32601, format!("method not found: {}", method))), Vec::new())
+        }
+    }
+}
+
+// returns None at the end of the input, the message or why its content is not json
+fn read_message<R: BufRead>(input: &mut R) -> Result<Option<Result<Json, Error>>, Error> {
+    let io_error = |error: ::std::io::Error| Error::Io("stdin".to_string(), error.to_string());
+    let mut length: Option<usize> = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).map_err(io_error)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if header.to_lowercase().starts_with("content-length:") {
+            length = header["content-length:".len()..].trim().parse::<usize>().ok();
+        }
+    }
+    let length = match length {
+        Some(length) => length,
+        None => return Err(Error::Json(0, "message without content-length".to_string()))
+    };
+    let mut content = vec![0u8; length];
+    input.read_exact(&mut content).map_err(io_error)?;
+    return Ok(Some(Json::parse(&String::from_utf8_lossy(&content))));
+}
+
+fn write_message<W: Write>(output: &mut W, message: &Json) -> Result<(), Error> {
+    let content = message.to_string();
+    return write!(output, "Content-Length: {}\r\n\r\n{}", content.len(), content)
+        .and_then(|_| output.flush())
+        .map_err(|error| Error::Io("stdout".to_string(), error.to_string()));
+}
+
+// answers requests until exit or the end of the input
+// returns: whether shutdown came before exit, like the protocol wants
+pub fn serve<R: BufRead, W: Write>(input: &mut R, output: &mut W) -> Result<bool, Error> {
+    let mut server = Server::new();
+    while let Some(message) = read_message(input)? {
+        // a malformed message gets a parse error without an id and the session goes on
+        let message = match message {
+            Ok(message) => message,
+            Err(error) => {
+                write_message(output, &Json::object(vec![
+                    ("jsonrpc", Json::from_text("2.0")), ("id", Json::Null),
+                    ("error", Json::object(vec![("code", Json::Number("-32700".to_string())), ("message", Json::String(error.to_string()))]))
+                ]))?;
+                continue;
+            }
+        };
+        let method = message.get("method").and_then(|method| method.as_str()).unwrap_or("").to_string();
+        if method == "exit" {
+            return Ok(server.shutdown);
+        }
+        let params = message.get("params").cloned().unwrap_or(Json::Null);
+        let (result, notifications) = server.handle(&method, &params);
+        for notification in &notifications {
+            write_message(output, notification)?;
+        }
+        // notifications have no id and get no response
+        if let Some(id) = message.get("id") {
+            let response = match result {
+                Ok(result) => Json::object(vec![("jsonrpc", Json::from_text("2.0")), ("id", id.clone()), ("result", result)]),
+                Err((code, description)) => Json::object(vec![
+                    ("jsonrpc", Json::from_text("2.0")), ("id", id.clone()),
+                    ("error", Json::object(vec![("code", Json::Number(code.to_string())), ("message", Json::String(description))]))
+                ])
+            };
+            write_message(output, &response)?;
+        }
+    }
+    return Ok(server.shutdown);
+}
\ No newline at end of file
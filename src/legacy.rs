@@ -0,0 +1,174 @@
+use vm::{Opcode, Type};
+use error::Error;
+use formatter;
+
+// the dialect of asm/*.old.asm and asm/hello_world.asm, one statement per line without procedures:
+//
+//     offset:834          origin of the program
+//     :loop               label
+//     push u8 'H'         mnemonic and type as separate words, characters as literals
+//     cp u8 1             cp copies a local
+//     jmp true :loop      condition as a separate word
+//     push_u8 A           newer mnemonics, numbers are hex with or without 0x
+//
+// translate turns such a file into one procedure of the current syntax, with org for the offset, named after its first label
+// or else name up to the first dot, so printc.old becomes printc
+// returns: the source, origin of the program when it had one
+pub fn translate(source: &str, name: &str) -> Result<(String, Option<u64>), Error> {
+    let mut statements: Vec<Vec<String>> = Vec::new();
+    for line in source.lines() {
+        statements.push(words(line)?);
+    }
+
+    // a label before the first operation names the procedure, references to it become &this.name
+    let first_label = statements.iter().find(|words| !words.is_empty() && !words[0].starts_with("offset:"))
+        .and_then(|words| if words[0].starts_with(':') { Some(words[0][1..].to_string()) } else { None });
+    let mut names: Vec<(String, String)> = Vec::new();
+    let name = name.split('.').next().unwrap_or(name);
+    let proc_name = sanitize(first_label.as_deref().unwrap_or(name), &names);
+    if let Some(label) = &first_label {
+        names.push((label.to_string(), proc_name.to_string()));
+    }
+    for words in &statements {
+        if words.len() == 1 && words[0].starts_with(':') && !names.iter().any(|(label, _)| *label == words[0][1..]) {
+            let renamed = sanitize(&words[0][1..], &names);
+            names.push((words[0][1..].to_string(), renamed));
+        }
+    }
+    let label_ref = |label: &str| -> Result<String, Error> {
+        match names.iter().find(|(original, _)| original == label) {
+            Some((original, renamed)) if Some(original) == first_label.as_ref() => return Ok(format!("&this.{}", renamed)),
+            Some((_, renamed)) => return Ok(format!("&{}", renamed)),
+            // labels of other files, like print_char of the console, are left for the assembler to report
+            None => return Ok(format!("&{}", sanitize(label, &names)))
+        }
+    };
+
+    let mut origin: Option<u64> = None;
+    let mut text = String::new();
+    let mut header = format!("proc {}:\n", proc_name);
+    for words in &statements {
+        if words.is_empty() {
+            text.push('\n');
+            continue;
+        }
+        if words[0].starts_with("offset:") && words.len() == 1 {
+            origin = Some(number(&words[0]["offset:".len()..])?);
+            header = format!("org {:#X}\n\n{}", origin.unwrap(), header);
+            continue;
+        }
+        if words[0].starts_with(':') && words.len() == 1 {
+            if Some(&words[0][1..].to_string()) != first_label.as_ref() {
+                text.push_str(&format!("{}:\n", &label_ref(&words[0][1..])?[1..]));
+            }
+            continue;
+        }
+
+        let (opcode, operands) = mnemonic(words)?;
+        let mut line = opcode.instruction().mnemonic.to_string();
+        for operand in operands {
+            line.push(' ');
+            if let Some(label) = operand.strip_prefix(':') {
+                line.push_str(&label_ref(label)?);
+            } else if operand.starts_with('&') {
+                line.push_str(operand);
+            } else {
+                line.push_str(&format!("{:#X}", number(operand)?));
+            }
+        }
+        text.push_str(&line);
+        text.push('\n');
+    }
+    let translated = format!("{}{}end proc\n", header, text);
+    return Ok((formatter::format(&translated)?, origin));
+}
+
+// splits on whitespace, except within character literals like ' '
+fn words(line: &str) -> Result<Vec<String>, Error> {
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in line.chars() {
+        if quoted {
+            word.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '\'' {
+                quoted = false;
+            }
+        } else if c == '\'' {
+            word.push(c);
+            quoted = true;
+        } else if c.is_whitespace() {
+            if !word.is_empty() {
+                words.push(word);
+                word = String::new();
+            }
+        } else {
+            word.push(c);
+        }
+    }
+    if quoted {
+        return Err(Error::InvalidToken(word));
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    return Ok(words);
+}
+
+// current names only have lowercase letters and digits and start with a letter
+fn sanitize(name: &str, taken: &[(String, String)]) -> String {
+    let mut sanitized: String = name.to_lowercase().chars().filter(|c| c.is_ascii_lowercase() || c.is_ascii_digit()).collect();
+    if !sanitized.starts_with(|c: char| c.is_ascii_lowercase()) {
+        sanitized = format!("l{}", sanitized);
+    }
+    let mut unique = sanitized.to_string();
+    let mut suffix = 2;
+    while taken.iter().any(|(_, renamed)| *renamed == unique) {
+        unique = format!("{}{}", sanitized, suffix);
+        suffix += 1;
+    }
+    return unique;
+}
+
+// 'c' with the escapes \0 \n \r \t \\ \', 0x1F, or hex without prefix
+fn number(text: &str) -> Result<u64, Error> {
+    if text.len() >= 3 && text.starts_with('\'') && text.ends_with('\'') {
+        let inner = &text[1..text.len()-1];
+        let value = match inner {
+            "\\0" => Some(0),
+            "\\n" => Some(0xA),
+            "\\r" => Some(0xD),
+            "\\t" => Some(0x9),
+            "\\\\" => Some(0x5C),
+            "\\'" => Some(0x27),
+            _ if inner.chars().count() == 1 => Some(inner.chars().next().unwrap() as u64),
+            _ => None
+        };
+        return value.ok_or(Error::InvalidToken(text.to_string()));
+    }
+    let digits = if text.starts_with("0x") || text.starts_with("0X") { &text[2..] } else { text };
+    return u64::from_str_radix(digits, 16).map_err(|_| Error::InvalidToken(text.to_string()));
+}
+
+// push u8, cp u8, jmp true and the current mnemonics
+// returns: opcode, operand words
+fn mnemonic(words: &[String]) -> Result<(Opcode, &[String]), Error> {
+    let first = if words[0].to_lowercase() == "cp" { "cpl".to_string() } else { words[0].to_lowercase() };
+    if let Some(second) = words.get(1) {
+        let second = second.to_lowercase();
+        if Type::from_name(&second).is_some() || second == "true" || second == "false" {
+            if let Some(opcode) = Opcode::from_mnemonic(&format!("{}_{}", first, second)) {
+                return Ok((opcode, &words[2..]));
+            }
+        }
+    }
+    match Opcode::from_mnemonic(&first) {
+        Some(opcode) => return Ok((opcode, &words[1..])),
+        None => return Err(Error::InvalidToken(words[0].to_string()))
+    }
+}
\ No newline at end of file
@@ -1,18 +1,5 @@
-pub enum Opcode {
-    Jmp,
-    Jmps,
-    JmpTrue,
-    CmpU8,
-    Spi,
-    Spd,
-    PushU8,
-    PushU64,
-    PopU8,
-    SetU8,
-    CplU8,
-    CpgU8,
-    Halt
-}
+use vm::{Opcode, Type};
+use error::Error;
 
 pub struct ExtProcRef {
     pub module: String,
@@ -23,12 +10,22 @@ pub enum Token {
     IntLiteral(u64),
     NewLine,
     Proc,
+    // org address, before the first procedure
+    Org,
     Opcode(Opcode),
     Label(String),
     LabelRef(String),
     ProcRef(String),
     ExtProcRef(ExtProcRef),
+    // procedure signatures
+    Identifier(String),
+    Type(Type),
+    ParenOpen,
+    ParenClose,
+    Comma,
+    Arrow,
     End,
+    #[allow(clippy::upper_case_acronyms)]
     EOF
 }
 
@@ -36,11 +33,11 @@ pub enum Token {
 fn next_token_text(source: &str) -> Option<(&str, &str)> {
     match source.find(|c: char| return c != ' ' && c != '\t') {
         Some(next_non_whitespace) => {
-            if source[next_non_whitespace..].starts_with('\n') {
+            if source[next_non_whitespace..].starts_with(|c: char| return c == '\n' || c == '(' || c == ')' || c == ',') {
                 return Some((&source[next_non_whitespace..(next_non_whitespace+1)], &source[next_non_whitespace+1..]));
             }
             else {
-                match source[next_non_whitespace..].find(|c: char| return c == ' ' || c == '\t' || c == '\n') {
+                match source[next_non_whitespace..].find(|c: char| return c == ' ' || c == '\t' || c == '\n' || c == '(' || c == ')' || c == ',') {
                     Some(token_end) => {
                         return Some((&source[next_non_whitespace..next_non_whitespace+token_end], &source[next_non_whitespace+token_end..]));
                     },
@@ -68,7 +65,7 @@ fn text_to_intliteraldec(text: &str) -> Option<Token> {
 }
 
 fn text_to_intliteralhex(text: &str) -> Option<Token> {
-    if !text.starts_with("0x") {
+    if !text.starts_with("0x") && !text.starts_with("0X") {
         return None
     }
     match u64::from_str_radix(&text[2..], 16) {
@@ -89,6 +86,14 @@ fn text_to_proc(text: &str) -> Option<Token> {
     }
 }
 
+fn text_to_org(text: &str) -> Option<Token> {
+    if text == "org" {
+        return Some(Token::Org);
+    } else {
+        return None;
+    }
+}
+
 fn text_to_end(text: &str) -> Option<Token> {
     if text == "end" {
         return Some(Token::End);
@@ -98,31 +103,31 @@ fn text_to_end(text: &str) -> Option<Token> {
 }
 
 fn text_to_label(text: &str) -> Option<Token> {
-    if text.len() > 1 && text.ends_with(":") && text[0..text.len()-2].chars().all(|c| c.is_numeric() || c.is_lowercase()) && text.chars().next().unwrap().is_lowercase() {
-        return Some(Token::Label(text[0..text.len()-1].to_string()));
-    }  else {
-        return None;
+    match text.strip_suffix(':') {
+        Some(name) if is_label_name(name) => return Some(Token::Label(name.to_string())),
+        _ => return None
     }
 }
 
 fn text_to_labelref(text: &str) -> Option<Token> {
-    if text.len() > 1 && text.starts_with("&") && text[1..text.len()-1].chars().all(|c| c.is_numeric() || c.is_lowercase()) && text.chars().nth(1).unwrap().is_lowercase() {
-        return Some(Token::LabelRef(text[1..text.len()].to_string()));
-    }  else {
-        return None;
+    match text.strip_prefix('&') {
+        Some(name) if is_label_name(name) => return Some(Token::LabelRef(name.to_string())),
+        _ => return None
     }
 }
 
+// a lowercase letter followed by lowercase letters and digits, checked by char so any utf-8 is safe
+fn is_label_name(name: &str) -> bool {
+    return name.chars().next().is_some_and(|c| c.is_lowercase()) && name.chars().all(|c| c.is_numeric() || c.is_lowercase());
+}
+
 fn text_to_procref(text: &str) -> Option<Token> {
     if text.len() > 3 && text.starts_with("&") {
         match text.find('.') {
             Some(dot_pos) => {
                 let module = &text[1..dot_pos];
                 let procedure = &text[dot_pos+1..];
-                if  !module.chars().all(|c| c.is_numeric() || c.is_lowercase()) ||
-                    !procedure.chars().all(|c| c.is_numeric() || c.is_lowercase()) ||
-                    !module.chars().next().unwrap().is_lowercase() ||
-                    !procedure.chars().next().unwrap().is_lowercase() {
+                if !is_label_name(module) || !is_label_name(procedure) {
                     return None;
                 }
                 if module != "this" {
@@ -145,10 +150,7 @@ fn text_to_extprocref(text: &str) -> Option<Token> {
             Some(dot_pos) => {
                 let module = &text[1..dot_pos];
                 let procedure = &text[dot_pos+1..];
-                if  !module.chars().all(|c| c.is_numeric() || c.is_lowercase()) ||
-                    !procedure.chars().all(|c| c.is_numeric() || c.is_lowercase()) ||
-                    !module.chars().next().unwrap().is_lowercase() ||
-                    !procedure.chars().next().unwrap().is_lowercase() {
+                if !is_label_name(module) || !is_label_name(procedure) {
                     return None;
                 }
                 return Some(Token::ExtProcRef(ExtProcRef{
@@ -165,25 +167,33 @@ fn text_to_extprocref(text: &str) -> Option<Token> {
     }
 }
 
+// mnemonics are not case sensitive
 fn text_to_opcode(text: &str) -> Option<Token> {
+    return Opcode::from_mnemonic(&text.to_lowercase()).map(Token::Opcode);
+}
+
+fn text_to_type(text: &str) -> Option<Token> {
+    return Type::from_name(text).map(Token::Type);
+}
+
+fn text_to_punctuation(text: &str) -> Option<Token> {
     match text {
-        "jmp" => Some(Token::Opcode(Opcode::Jmp)),
-        "jmps" => Some(Token::Opcode(Opcode::Jmps)),
-        "jmp_true" => Some(Token::Opcode(Opcode::JmpTrue)),
-        "cmp_u8" => Some(Token::Opcode(Opcode::CmpU8)),
-        "spi" => Some(Token::Opcode(Opcode::Spi)),
-        "spd" => Some(Token::Opcode(Opcode::Spd)),
-        "push_u8" => Some(Token::Opcode(Opcode::PushU8)),
-        "push_u64" => Some(Token::Opcode(Opcode::PushU64)),
-        "pop_u8" => Some(Token::Opcode(Opcode::PopU8)),
-        "set_u8" => Some(Token::Opcode(Opcode::SetU8)),
-        "cpl_u8" => Some(Token::Opcode(Opcode::CplU8)),
-        "cpg_u8" => Some(Token::Opcode(Opcode::CpgU8)),
-        "halt" => Some(Token::Opcode(Opcode::Halt)),
+        "(" => Some(Token::ParenOpen),
+        ")" => Some(Token::ParenClose),
+        "," => Some(Token::Comma),
+        "->" => Some(Token::Arrow),
         _ => None
     }
 }
 
+fn text_to_identifier(text: &str) -> Option<Token> {
+    if is_label_name(text) {
+        return Some(Token::Identifier(text.to_string()));
+    } else {
+        return None;
+    }
+}
+
 fn text_to_newline(text: &str) -> Option<Token> {
     if text == "\n" {
         Some(Token::NewLine)
@@ -203,18 +213,21 @@ fn text_to_token(text: &str) -> Option<Token> {
         text_to_labelref,
         text_to_procref,
         text_to_extprocref,
-        text_to_end
+        text_to_end,
+        text_to_org,
+        text_to_type,
+        text_to_punctuation,
+        text_to_identifier
     ];
     for converter in converters.iter() {
-        let token = converter(text);
-        if token.is_some() {
-            return Some(token.unwrap());
+        if let Some(token) = converter(text) {
+            return Some(token);
         }
     }
     return None;
 }
 
-fn convert_newlines(source: &str) -> String {
+pub fn convert_newlines(source: &str) -> String {
     let mut result = source.replace("\r\n", "\n");
     result = result.replace("\n\r", "\n");
     result = result.replace("\r", "\n");
@@ -222,18 +235,45 @@ fn convert_newlines(source: &str) -> String {
     return result;
 }
 
-pub fn lex(source: &str) -> Vec<Token> {
+// returns: code, comment from the ; to the end of the line
+pub fn split_comment(line: &str) -> (&str, Option<&str>) {
+    match line.find(';') {
+        Some(start) => return (&line[..start], Some(&line[start..])),
+        None => return (line, None)
+    }
+}
+
+// tokens of a single line without comment, together with their text
+pub fn lex_line(code: &str) -> Result<Vec<(&str, Token)>, Error> {
+    let mut tokens = Vec::new();
+    let mut source_leftover = code;
+    while let Some((token_text, leftover)) = next_token_text(source_leftover) {
+        match text_to_token(token_text) {
+            Some(token) => tokens.push((token_text, token)),
+            None => return Err(Error::InvalidToken(token_text.to_string()))
+        }
+        source_leftover = leftover;
+    }
+    return Ok(tokens);
+}
+
+fn strip_comments(source: &str) -> String {
+    let lines: Vec<&str> = source.split('\n').map(|line| split_comment(line).0).collect();
+    return lines.join("\n");
+}
+
+pub fn lex(source: &str) -> Result<Vec<Token>, Error> {
     let mut tokens = Vec::new();
 
     let mut tokens_available = true;
-    let mut source_leftover: &str = &convert_newlines(source);
+    let mut source_leftover: &str = &strip_comments(&convert_newlines(source));
     while tokens_available {
         let token_res = next_token_text(source_leftover);
         match token_res {
             Some((token_text, leftover)) => {
                 match text_to_token(token_text) {
                     Some(token) => tokens.push(token),
-                    None => panic!("Invalid token: {}", token_text)
+                    None => return Err(Error::InvalidToken(token_text.to_string()))
                 }
                 source_leftover = leftover;
             },
@@ -244,5 +284,5 @@ pub fn lex(source: &str) -> Vec<Token> {
     }
     tokens.push(Token::EOF);
 
-    return tokens;
+    return Ok(tokens);
 }
\ No newline at end of file
@@ -0,0 +1,315 @@
+// returning with return and spelling out field: field in struct literals is how this crate is written
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+extern crate byteorder;
+
+pub mod ast;
+pub mod vm;
+pub mod error;
+pub mod map;
+pub mod format_vmw;
+pub mod disassembler;
+pub mod stack;
+pub mod cfg;
+pub mod linker;
+pub mod machine;
+pub mod formatter;
+pub mod project;
+pub mod watch;
+pub mod json;
+pub mod language_server;
+pub mod legacy;
+pub mod image;
+pub mod resolver;
+pub mod web;
+mod binary;
+mod lexer;
+mod parser;
+mod checker;
+mod lower;
+mod optimizer;
+mod generator;
+mod preprocessor;
+
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+pub use error::{Error, Warning};
+pub use format_vmw::VMW;
+pub use map::SymbolMap;
+pub use image::{Format, Image};
+use map::SymbolKind;
+use machine::Machine;
+use resolver::{Resolver, FileResolver};
+
+#[derive(Default)]
+pub struct Options {
+    // peephole optimizations between parser and generator
+    pub optimize: bool,
+    // relative jumps for vms that support them
+    pub short_jumps: bool,
+    // call and ret for vms without them
+    pub lower_calls: bool,
+    // searched for includes after the directory of the including file
+    pub include_dirs: Vec<String>,
+    // name, value, later defines win
+    pub defines: Vec<(String, String)>,
+    // sources are in the dialect of asm/*.old.asm, see legacy::translate
+    pub legacy: bool
+}
+
+impl Options {
+    pub fn new() -> Options {
+        return Options{optimize: false, short_jumps: false, lower_calls: false, include_dirs: Vec::new(), defines: Vec::new(), legacy: false};
+    }
+}
+
+pub struct Assembly {
+    pub vmw: VMW,
+    pub map: SymbolMap,
+    pub warnings: Vec<Warning>,
+    // what the optimizer changed, prefixed with the procedure
+    pub optimizations: Vec<String>,
+    // every file pulled in through include
+    pub includes: Vec<String>,
+    // set with org, where to_flat places the code
+    pub origin: Option<u64>
+}
+
+impl Assembly {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        return self.vmw.to_bytes();
+    }
+
+    // raw code with absolute addresses for loading at the origin, 0 without org
+    pub fn to_flat(&self) -> Result<Vec<u8>, Error> {
+        return self.vmw.to_flat(self.origin.unwrap_or(0));
+    }
+
+    // every operation with its offset and encoding, preceded by the procedures and labels that start at it
+    pub fn listing(&self) -> Result<String, Error> {
+        let names: Vec<(u64, String)> = self.map.symbols.iter()
+            .filter(|symbol| !matches!(symbol.kind, SymbolKind::External))
+            .map(|symbol| (symbol.offset, symbol.name.to_string()))
+            .collect();
+        return disassembler::listing(&self.vmw.binary, &names);
+    }
+
+    // the module in the format of image to output, the symbol map and the listing to their files when they are given
+    // name goes into the header of s-records, without a base the code starts at org
+    pub fn write(&self, image: &Image, output: &str, map: Option<&str>, listing: Option<&str>, name: &str) -> Result<(), Error> {
+        write_output(output, &image.write(&self.vmw, Some(&self.map), self.origin, name)?)?;
+        if let Some(map) = map {
+            write_output(map, self.map.to_text().as_bytes())?;
+        }
+        if let Some(listing) = listing {
+            write_output(listing, self.listing()?.as_bytes())?;
+        }
+        return Ok(());
+    }
+}
+
+// the file name without directories and extension, which is how other modules refer to it
+pub fn module_name(path: &str) -> String {
+    return Path::new(path).file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or(path.to_string());
+}
+
+// every operation of a module, or of code in one of the formats with absolute addresses
+// procedure names are only known for modules, flat code starts at base
+pub fn disassemble(contents: &[u8], format: Format, base: u64) -> Result<String, Error> {
+    match format {
+        Format::Vmw | Format::Json => {
+            let module = VMW::from_contents(contents)?.0;
+            let names: Vec<(u64, String)> = module.procedures.iter().map(|(name, offset, _)| (*offset, name.to_string())).collect();
+            return disassembler::listing(&module.binary, &names);
+        },
+        _ => {
+            let (start, code) = image::read_code(contents, format, base)?;
+            return disassembler::disassemble_at(&code, start);
+        }
+    }
+}
+
+// everything reported about a source that could not be assembled
+pub struct Diagnostics {
+    pub errors: Vec<Error>,
+    pub warnings: Vec<Warning>
+}
+
+impl Diagnostics {
+    fn error(error: Error) -> Diagnostics {
+        return Diagnostics{errors: vec![error], warnings: Vec::new()};
+    }
+}
+
+// like read_file, - reads stdin
+pub fn read_input(path: &str) -> Result<Vec<u8>, Error> {
+    let mut contents: Vec<u8> = Vec::new();
+    let result = if path == "-" { io::stdin().read_to_end(&mut contents) } else { File::open(path).and_then(|mut f| f.read_to_end(&mut contents)) };
+    match result {
+        Ok(_) => return Ok(contents),
+        Err(error) => return Err(Error::Io(path.to_string(), error.to_string()))
+    }
+}
+
+// - writes stdout
+pub fn write_output(path: &str, contents: &[u8]) -> Result<(), Error> {
+    let result = if path == "-" { io::stdout().write_all(contents) } else { File::create(path).and_then(|mut f| f.write_all(contents)) };
+    match result {
+        Ok(_) => return Ok(()),
+        Err(error) => return Err(Error::Io(path.to_string(), error.to_string()))
+    }
+}
+
+// json documents or the binary layout, - reads stdin
+pub fn read_module(path: &str) -> Result<(VMW, Option<SymbolMap>), Error> {
+    return VMW::from_contents(&read_input(path)?);
+}
+
+// module name, module, the way linker::link takes them
+pub type NamedModules = Vec<(String, VMW)>;
+
+// inputs ending in .asm are assembled first, everything else is read as a vmw module
+// returns: every module named after its file, the warnings of the sources
+pub fn load_modules(inputs: &[String], options: &Options) -> Result<(NamedModules, Vec<Warning>), Diagnostics> {
+    let mut modules: Vec<(String, VMW)> = Vec::new();
+    let mut warnings: Vec<Warning> = Vec::new();
+    for input in inputs {
+        if input.ends_with(".asm") {
+            match assemble_file(input, options) {
+                Ok(mut assembly) => {
+                    warnings.append(&mut assembly.warnings);
+                    modules.push((module_name(input), assembly.vmw));
+                },
+                Err(mut diagnostics) => {
+                    warnings.append(&mut diagnostics.warnings);
+                    return Err(Diagnostics{errors: diagnostics.errors, warnings: warnings});
+                }
+            }
+        } else {
+            match read_module(input) {
+                Ok((module, _)) => modules.push((module_name(input), module)),
+                Err(error) => return Err(Diagnostics{errors: vec![error], warnings: warnings})
+            }
+        }
+    }
+    return Ok((modules, warnings));
+}
+
+// links the modules and runs them on the reference vm for at most steps operations
+// returns: the console output, even when running failed, and the number of operations it took
+pub fn run(modules: &NamedModules, steps: u64) -> (Vec<u8>, Result<u64, Error>) {
+    let mut machine = match linker::link(modules).and_then(|linked| Machine::load(&linked)) {
+        Ok(machine) => machine,
+        Err(error) => return (Vec::new(), Err(error))
+    };
+    let result = machine.run(steps);
+    return (machine.output, result);
+}
+
+pub fn read_file(path: &str) -> Result<String, Error> {
+    let mut contents = String::new();
+    match File::open(path).and_then(|mut f| f.read_to_string(&mut contents)) {
+        Ok(_) => return Ok(contents),
+        Err(error) => return Err(Error::Io(path.to_string(), error.to_string()))
+    }
+}
+
+pub fn parse(source: &str) -> Result<ast::Tree, Error> {
+    let tokens = lexer::lex(source)?;
+    return parser::parse(&tokens);
+}
+
+// parses after resolving includes relative to path and replacing defines
+// returns: the tree, every included file
+pub fn parse_with(source: &str, path: &str, options: &Options) -> Result<(ast::Tree, Vec<String>), Error> {
+    return parse_with_resolver(source, path, options, &FileResolver);
+}
+
+// like parse_with, with includes read through resolver instead of from disk
+pub fn parse_with_resolver(source: &str, path: &str, options: &Options, resolver: &dyn Resolver) -> Result<(ast::Tree, Vec<String>), Error> {
+    let translated;
+    let source = if options.legacy {
+        let name = Path::new(path).file_stem().and_then(|stem| stem.to_str()).unwrap_or("main");
+        translated = legacy::translate(source, name)?.0;
+        &translated
+    } else {
+        source
+    };
+    let (preprocessed, includes) = preprocessor::preprocess(source, path, &options.include_dirs, &options.defines, resolver)?;
+    return Ok((parse(&preprocessed)?, includes));
+}
+
+// rewrites call and ret for vms without them, assemble does this with Options::lower_calls
+pub fn lower_calls(tree: &mut ast::Tree) {
+    lower::lower_calls(tree);
+}
+
+// errors and warnings of a source without generating code
+pub fn check(tree: &ast::Tree) -> Diagnostics {
+    let warnings = analyse(tree);
+    match checker::check(tree) {
+        Ok(()) => return Diagnostics{errors: Vec::new(), warnings: warnings},
+        Err(errors) => return Diagnostics{errors: errors, warnings: warnings}
+    }
+}
+
+// like check, for a source that is parsed and lowered the way assemble_named does it
+// returns: the tree that was checked, every included file, what check reports
+pub fn check_named(source: &str, path: &str, options: &Options) -> Result<(ast::Tree, Vec<String>, Diagnostics), Error> {
+    let (mut tree, includes) = parse_with(source, path, options)?;
+    if options.lower_calls {
+        lower::lower_calls(&mut tree);
+    }
+    let diagnostics = check(&tree);
+    return Ok((tree, includes, diagnostics));
+}
+
+// call site, control flow and stack height warnings
+pub fn analyse(tree: &ast::Tree) -> Vec<Warning> {
+    let mut warnings = checker::check_calls(tree);
+    warnings.append(&mut cfg::analyse(tree).1);
+    warnings.append(&mut stack::analyse(tree).1);
+    return warnings;
+}
+
+// includes are looked up relative to the working directory
+pub fn assemble(source: &str, options: &Options) -> Result<Assembly, Diagnostics> {
+    return assemble_named(source, "", options);
+}
+
+// like assemble, with includes looked up next to path
+pub fn assemble_named(source: &str, path: &str, options: &Options) -> Result<Assembly, Diagnostics> {
+    return assemble_with_resolver(source, path, options, &FileResolver);
+}
+
+// like assemble_named, with includes read through resolver instead of from disk
+pub fn assemble_with_resolver(source: &str, path: &str, options: &Options, resolver: &dyn Resolver) -> Result<Assembly, Diagnostics> {
+    let (mut tree, includes) = parse_with_resolver(source, path, options, resolver).map_err(Diagnostics::error)?;
+    if options.lower_calls {
+        lower::lower_calls(&mut tree);
+    }
+
+    let warnings = analyse(&tree);
+    match checker::check(&tree) {
+        Ok(()) => {},
+        Err(errors) => return Err(Diagnostics{errors: errors, warnings: warnings})
+    }
+
+    let mut optimizations: Vec<String> = Vec::new();
+    if options.optimize {
+        optimizations = optimizer::optimize(&mut tree);
+    }
+
+    match generator::generate(&tree, options.short_jumps) {
+        Ok((vmw, map)) => return Ok(Assembly{vmw: vmw, map: map, warnings: warnings, optimizations: optimizations, includes: includes, origin: tree.origin}),
+        Err(error) => return Err(Diagnostics{errors: vec![error], warnings: warnings})
+    }
+}
+
+pub fn assemble_file(path: &str, options: &Options) -> Result<Assembly, Diagnostics> {
+    let source = read_file(path).map_err(Diagnostics::error)?;
+    return assemble_named(&source, path, options);
+}
\ No newline at end of file
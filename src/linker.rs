@@ -0,0 +1,63 @@
+use format_vmw::{VMW, ExternalProcedure};
+use error::Error;
+use binary::{read_uint, overwrite_u64};
+
+// appends the binaries of the modules in order and resolves calls between them
+// modules are named like in external references, e.g. console for &console.printc
+// local addresses are moved along with their module, resolved calls become local addresses,
+// calls to modules that are not part of the link stay external
+pub fn link(modules: &Vec<(String, VMW)>) -> Result<VMW, Error> {
+    let mut bin: Vec<u8> = Vec::new();
+    let mut procedures: Vec<(String, u64, String)> = Vec::new();
+    let mut local_addresses: Vec<u64> = Vec::new();
+    let mut external_procedures: Vec<(ExternalProcedure, u64)> = Vec::new();
+
+    let mut bases: Vec<u64> = Vec::new();
+    for (_, module) in modules {
+        bases.push(bin.len() as u64);
+        bin.extend_from_slice(&module.binary);
+    }
+
+    for ((_, module), base) in modules.iter().zip(bases.iter()) {
+        for (name, offset, signature) in &module.procedures {
+            if procedures.iter().any(|procedure| procedure.0 == *name) {
+                return Err(Error::DuplicateLabel(name.to_string()));
+            }
+            procedures.push((name.to_string(), base + offset, signature.to_string()));
+        }
+
+        for local_address in &module.local_addresses {
+            let at = (base + local_address) as usize;
+            if at + 8 > bin.len() {
+                return Err(Error::InvalidModule(format!("local address {:#x} is outside of the binary", local_address)));
+            }
+            let value = read_uint(&bin[at..], 8) + base;
+            overwrite_u64(&mut bin[at..], &value);
+            local_addresses.push(at as u64);
+        }
+
+        for (external, offset) in &module.external_procedures {
+            let at = base + offset;
+            if at + 8 > bin.len() as u64 {
+                return Err(Error::InvalidModule(format!("external call {:#x} is outside of the binary", offset)));
+            }
+            let target = match modules.iter().position(|(name, _)| *name == external.module) {
+                Some(target) => target,
+                None => {
+                    external_procedures.push((ExternalProcedure{module: external.module.to_string(), procedure: external.procedure.to_string()}, at));
+                    continue;
+                }
+            };
+            match modules[target].1.procedures.iter().find(|procedure| procedure.0 == external.procedure) {
+                Some(procedure) => {
+                    overwrite_u64(&mut bin[at as usize..], &(bases[target] + procedure.1));
+                    local_addresses.push(at);
+                },
+                None => return Err(Error::UnknownProcedure(format!("{}.{}", external.module, external.procedure)))
+            }
+        }
+    }
+
+    local_addresses.sort();
+    return Ok(VMW::new(bin, procedures, local_addresses, external_procedures));
+}
\ No newline at end of file
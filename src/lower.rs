@@ -0,0 +1,43 @@
+use ast::{Tree, Operation, Address};
+use vm::Opcode;
+
+// rewrites call and ret for vms without them:
+// call becomes push_u64 of a generated return label followed by jmp, ret becomes jmps
+pub fn lower_calls(source: &mut Tree) {
+    for (_, procedure) in source.procedures.iter_mut() {
+        let operations = std::mem::take(&mut procedure.operations);
+        let labels = std::mem::take(&mut procedure.labels);
+        let operation_count = operations.len();
+        let mut next_label: usize = 0;
+        let mut returns: usize = 0;
+
+        let mut operations = operations.into_iter();
+        for op_index in 0..(operation_count + 1) {
+            while next_label < labels.len() && labels[next_label].1 == op_index {
+                procedure.labels.push((labels[next_label].0.to_string(), procedure.operations.len()));
+                next_label += 1;
+            }
+            let operation = match operations.next() {
+                Some(operation) => operation,
+                None => break
+            };
+
+            match operation.opcode {
+                Opcode::Call => {
+                    // generated labels contain an underscore, so they can not collide with labels in the source
+                    let return_label = format!("call_return_{}", returns);
+                    returns += 1;
+                    procedure.operations.push(Operation{opcode: Opcode::PushU64, operands: vec![Address::Label(return_label.to_string())]});
+                    procedure.operations.push(Operation{opcode: Opcode::Jmp, operands: operation.operands});
+                    procedure.labels.push((return_label, procedure.operations.len()));
+                },
+                Opcode::Ret => {
+                    procedure.operations.push(Operation{opcode: Opcode::Jmps, operands: Vec::new()});
+                },
+                _ => {
+                    procedure.operations.push(operation);
+                }
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,257 @@
+use format_vmw::VMW;
+use vm::Opcode;
+use error::Error;
+use binary::{read_uint, write_uint};
+
+// a reference implementation of the vm for running modules without a browser
+// the module is loaded at LOAD_ADDRESS into a flat memory, the stack lives apart from it
+pub const MEMORY_SIZE: usize = 0x10000;
+pub const LOAD_ADDRESS: u64 = 0x1000;
+pub const STACK_SIZE: usize = 0x10000;
+// console device: setting the ready byte prints the character byte and clears ready again
+pub const CONSOLE_CHARACTER: u64 = 0xBB8;
+pub const CONSOLE_READY: u64 = 0xBB9;
+
+pub struct Machine {
+    pub memory: Vec<u8>,
+    pub stack: Vec<u8>,
+    pub pc: u64,
+    pub halted: bool,
+    // everything printed on the console
+    pub output: Vec<u8>
+}
+
+fn sign_extend(value: u64, width: usize) -> i64 {
+    let shift = 64 - width as u32 * 8;
+    return ((value << shift) as i64) >> shift;
+}
+
+// mask for the lowest width bytes
+fn mask(width: usize) -> u64 {
+    if width == 8 {
+        return u64::MAX;
+    }
+    return (1u64 << (width * 8)) - 1;
+}
+
+impl Machine {
+    // execution starts at proc start, or the first procedure if there is none
+    pub fn load(module: &VMW) -> Result<Machine, Error> {
+        let binary = module.to_flat(LOAD_ADDRESS)?;
+        let entry = match module.procedures.iter().find(|procedure| procedure.0 == "start").or(module.procedures.first()) {
+            Some(procedure) => procedure.1,
+            None => return Err(Error::InvalidModule("there is no procedure to start".to_string()))
+        };
+        if LOAD_ADDRESS as usize + module.binary.len() > MEMORY_SIZE {
+            return Err(Error::InvalidModule("binary does not fit in memory".to_string()));
+        }
+
+        let mut memory = vec![0u8; MEMORY_SIZE];
+        let start = LOAD_ADDRESS as usize;
+        memory[start..start + binary.len()].copy_from_slice(&binary);
+        return Ok(Machine{memory: memory, stack: Vec::new(), pc: LOAD_ADDRESS + entry, halted: false, output: Vec::new()});
+    }
+
+    // runs until halt, fails when that takes more than max_steps operations
+    pub fn run(&mut self, max_steps: u64) -> Result<u64, Error> {
+        let mut steps: u64 = 0;
+        while !self.halted {
+            if steps == max_steps {
+                return Err(Error::Fault(self.pc, format!("still running after {} operations", max_steps)));
+            }
+            self.step()?;
+            steps += 1;
+        }
+        return Ok(steps);
+    }
+
+    fn fault<T>(&self, description: &str) -> Result<T, Error> {
+        return Err(Error::Fault(self.pc, description.to_string()));
+    }
+
+    fn read(&self, address: u64, width: usize) -> Result<u64, Error> {
+        if address > (self.memory.len() - width) as u64 {
+            return self.fault(&format!("read from {:#x} is outside of memory", address));
+        }
+        return Ok(read_uint(&self.memory[address as usize..], width));
+    }
+
+    fn write(&mut self, address: u64, value: u64, width: usize) -> Result<(), Error> {
+        if address > (self.memory.len() - width) as u64 {
+            return self.fault(&format!("write to {:#x} is outside of memory", address));
+        }
+        let mut bytes: Vec<u8> = Vec::new();
+        write_uint(&mut bytes, value & mask(width), width);
+        self.memory[address as usize..address as usize + width].copy_from_slice(&bytes);
+        if address <= CONSOLE_READY && CONSOLE_READY < address + width as u64 && self.memory[CONSOLE_READY as usize] != 0 {
+            let character = self.memory[CONSOLE_CHARACTER as usize];
+            self.output.push(character);
+            self.memory[CONSOLE_READY as usize] = 0;
+        }
+        return Ok(());
+    }
+
+    fn push(&mut self, value: u64, width: usize) -> Result<(), Error> {
+        if self.stack.len() + width > STACK_SIZE {
+            return self.fault("stack overflow");
+        }
+        write_uint(&mut self.stack, value & mask(width), width);
+        return Ok(());
+    }
+
+    fn pop(&mut self, width: usize) -> Result<u64, Error> {
+        if self.stack.len() < width {
+            return self.fault("stack underflow");
+        }
+        let top = self.stack.len() - width;
+        let value = read_uint(&self.stack[top..], width);
+        self.stack.truncate(top);
+        return Ok(value);
+    }
+
+    // pops the right hand side, then the left hand side
+    fn pop_pair(&mut self, width: usize) -> Result<(u64, u64), Error> {
+        let rhs = self.pop(width)?;
+        let lhs = self.pop(width)?;
+        return Ok((lhs, rhs));
+    }
+
+    pub fn step(&mut self) -> Result<(), Error> {
+        let value = self.read(self.pc, 2)? as u16;
+        let opcode = match Opcode::from_value(value) {
+            Some(opcode) => opcode,
+            None => return self.fault(&format!("unknown opcode {}", value))
+        };
+        let instruction = opcode.instruction();
+        let operand = match instruction.operands.first() {
+            Some(operand) => self.read(self.pc + 2, operand.width)?,
+            None => 0
+        };
+        let next = self.pc + instruction.size();
+        let mut jump: Option<u64> = None;
+
+        match opcode {
+            Opcode::Jmp => jump = Some(operand),
+            Opcode::Jmps | Opcode::Ret => jump = Some(self.pop(8)?),
+            Opcode::Call => {
+                self.push(next, 8)?;
+                jump = Some(operand);
+            },
+            Opcode::JmpTrue | Opcode::JmpFalse | Opcode::JmpTrueRel8 | Opcode::JmpTrueRel16 | Opcode::JmpFalseRel8 | Opcode::JmpFalseRel16 => {
+                let condition = match self.stack.last() {
+                    Some(top) => *top != 0,
+                    None => return self.fault("stack underflow")
+                };
+                let on_true = matches!(opcode, Opcode::JmpTrue | Opcode::JmpTrueRel8 | Opcode::JmpTrueRel16);
+                if condition == on_true {
+                    jump = Some(match opcode {
+                        Opcode::JmpTrue | Opcode::JmpFalse => operand,
+                        _ => (next as i64 + sign_extend(operand, instruction.operands[0].width)) as u64
+                    });
+                }
+            },
+            Opcode::JmpRel8 | Opcode::JmpRel16 => jump = Some((next as i64 + sign_extend(operand, instruction.operands[0].width)) as u64),
+            Opcode::Spi => {
+                if operand > (STACK_SIZE - self.stack.len()) as u64 {
+                    return self.fault("stack overflow");
+                }
+                let height = self.stack.len() + operand as usize;
+                self.stack.resize(height, 0);
+            },
+            Opcode::Spd => {
+                if (self.stack.len() as u64) < operand {
+                    return self.fault("stack underflow");
+                }
+                let height = self.stack.len() - operand as usize;
+                self.stack.truncate(height);
+            },
+            Opcode::PushU8 | Opcode::PushU16 | Opcode::PushU32 | Opcode::PushU64 => self.push(operand, instruction.pushes as usize)?,
+            Opcode::PopU8 | Opcode::PopU16 | Opcode::PopU32 | Opcode::PopU64 => { self.pop(instruction.pops as usize)?; },
+            Opcode::SetU8 | Opcode::SetU16 | Opcode::SetU32 | Opcode::SetU64 => {
+                let width = instruction.pops as usize;
+                let value = self.pop(width)?;
+                self.write(operand, value, width)?;
+            },
+            // copies width bytes starting operand bytes below the top of the stack
+            Opcode::CplU8 | Opcode::CplU16 | Opcode::CplU32 | Opcode::CplU64 => {
+                let width = instruction.pushes as usize;
+                if operand > self.stack.len() as u64 || operand < width as u64 {
+                    return self.fault(&format!("copy from {} bytes below the top of the stack is outside of the stack", operand));
+                }
+                let start = self.stack.len() - operand as usize;
+                let value = read_uint(&self.stack[start..], width);
+                self.push(value, width)?;
+            },
+            Opcode::CpgU8 | Opcode::CpgU16 | Opcode::CpgU32 | Opcode::CpgU64 => {
+                let width = instruction.pushes as usize;
+                let value = self.read(operand, width)?;
+                self.push(value, width)?;
+            },
+            Opcode::Halt => self.halted = true,
+            _ => {
+                let result = self.compute(opcode)?;
+                self.push(result, instruction.pushes as usize)?;
+            }
+        }
+
+        self.pc = match jump {
+            Some(target) => target,
+            None => next
+        };
+        return Ok(());
+    }
+
+    // arithmetic, bitwise operations and comparisons, returns the value to push
+    fn compute(&mut self, opcode: Opcode) -> Result<u64, Error> {
+        let instruction = opcode.instruction();
+        match opcode {
+            Opcode::NotU8 | Opcode::NotU64 => return Ok(!self.pop(instruction.pops as usize)?),
+            Opcode::ShlU8 | Opcode::ShrU8 | Opcode::ShlU64 | Opcode::ShrU64 => {
+                let amount = self.pop(1)? as u32;
+                let width = instruction.pushes as usize;
+                let value = self.pop(width)?;
+                if amount >= width as u32 * 8 {
+                    return Ok(0);
+                }
+                match opcode {
+                    Opcode::ShlU8 | Opcode::ShlU64 => return Ok(value << amount),
+                    _ => return Ok(value >> amount)
+                }
+            },
+            _ => {}
+        }
+
+        let width = instruction.pops as usize / 2;
+        let (lhs, rhs) = self.pop_pair(width)?;
+        let (signed_lhs, signed_rhs) = (sign_extend(lhs, width), sign_extend(rhs, width));
+        let result = match opcode {
+            Opcode::AddU8 | Opcode::AddU64 => lhs.wrapping_add(rhs),
+            Opcode::SubU8 | Opcode::SubU64 => lhs.wrapping_sub(rhs),
+            Opcode::MulU8 | Opcode::MulU64 => lhs.wrapping_mul(rhs),
+            Opcode::DivU8 | Opcode::DivU64 | Opcode::ModU8 | Opcode::ModU64 => {
+                if rhs == 0 {
+                    return self.fault("division by zero");
+                }
+                match opcode {
+                    Opcode::DivU8 | Opcode::DivU64 => lhs / rhs,
+                    _ => lhs % rhs
+                }
+            },
+            Opcode::AndU8 | Opcode::AndU64 => lhs & rhs,
+            Opcode::OrU8 | Opcode::OrU64 => lhs | rhs,
+            Opcode::XorU8 | Opcode::XorU64 => lhs ^ rhs,
+            Opcode::CmpU8 | Opcode::CmpU64 => (lhs == rhs) as u64,
+            Opcode::CmpNeU8 | Opcode::CmpNeU64 => (lhs != rhs) as u64,
+            Opcode::CmpLtU8 | Opcode::CmpLtU64 => (lhs < rhs) as u64,
+            Opcode::CmpLeU8 | Opcode::CmpLeU64 => (lhs <= rhs) as u64,
+            Opcode::CmpGtU8 | Opcode::CmpGtU64 => (lhs > rhs) as u64,
+            Opcode::CmpGeU8 | Opcode::CmpGeU64 => (lhs >= rhs) as u64,
+            Opcode::CmpLtI8 | Opcode::CmpLtI64 => (signed_lhs < signed_rhs) as u64,
+            Opcode::CmpLeI8 | Opcode::CmpLeI64 => (signed_lhs <= signed_rhs) as u64,
+            Opcode::CmpGtI8 | Opcode::CmpGtI64 => (signed_lhs > signed_rhs) as u64,
+            Opcode::CmpGeI8 | Opcode::CmpGeI64 => (signed_lhs >= signed_rhs) as u64,
+            _ => return self.fault(&format!("{} is not implemented", instruction.mnemonic))
+        };
+        return Ok(result);
+    }
+}
\ No newline at end of file
@@ -1,38 +1,579 @@
+// returning with return and spelling out field: field in struct literals is how this crate is written
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
 use std::env;
-use std::fs::File;
+use std::process;
+use std::io;
 use std::io::prelude::*;
+use std::path::Path;
+
+extern crate vmw_assembler;
+use vmw_assembler::{Options, Error, Warning, Format, Image, stack, cfg, linker, formatter, language_server, image, watch};
+use vmw_assembler::{module_name, read_input, write_output, read_module, load_modules};
+use vmw_assembler::project::Project;
+
+// exit codes
+const SUCCESS: i32 = 0;
+// the input has errors, or running it failed
+const FAILURE: i32 = 1;
+const USAGE: i32 = 2;
+
+struct Command {
+    name: &'static str,
+    usage: &'static str,
+    description: &'static str,
+    // options without a value
+    flags: &'static [&'static str],
+    // options followed by a value
+    values: &'static [&'static str],
+    // gets its command for usage errors
+    run: fn(&Command, &Arguments) -> i32
+}
+
+const COMMANDS: &[Command] = &[
+    Command{name: "assemble", usage: "assemble [options] infile", description: "assembles a source into a vmw module",
+        flags: &["-O", "-v", "-g", "--short-jumps", "--lower-calls", "--legacy", "--watch", "--base64"], values: &["-o", "-I", "-D", "--listing", "--map", "--format", "--base"], run: assemble},
+    Command{name: "disassemble", usage: "disassemble [options] infile", description: "prints the operations of a vmw module or a flat binary",
+        flags: &["--flat"], values: &["-o", "--format", "--base"], run: disassemble},
+    Command{name: "link", usage: "link [options] infile...", description: "links vmw modules into one, resolving calls between them, modules are named after their file",
+        flags: &["--base64"], values: &["-o", "--format", "--base"], run: link},
+    Command{name: "dump", usage: "dump infile", description: "prints the procedures, external calls and local addresses of a vmw module",
+        flags: &[], values: &[], run: dump},
+    Command{name: "run", usage: "run [options] infile...", description: "links and runs sources or vmw modules on the reference vm, console output goes to stdout",
+        flags: &["-O", "--short-jumps", "--lower-calls", "--legacy"], values: &["-I", "-D", "--steps"], run: run},
+    Command{name: "check", usage: "check [options] infile", description: "reports errors and warnings without writing anything",
+        flags: &["--lower-calls", "--legacy", "--stack", "--cfg", "--watch"], values: &["-I", "-D"], run: check},
+    Command{name: "fmt", usage: "fmt [options] infile...", description: "writes a source in the canonical layout, or with --check lists the sources that are not",
+        flags: &["--check"], values: &["-o"], run: fmt},
+    Command{name: "build", usage: "build [options] [manifest]", description: "assembles the modules of a project that changed and links them, the manifest is vmw.toml by default",
+        flags: &["--force", "--watch"], values: &[], run: build},
+    Command{name: "lsp", usage: "lsp", description: "runs a language server over stdin and stdout for editors",
+        flags: &[], values: &[], run: lsp},
+    Command{name: "migrate", usage: "migrate [options] infile", description: "rewrites a source of the legacy dialect, like asm/*.old.asm, into one procedure of the current syntax",
+        flags: &[], values: &["-o"], run: migrate},
+    Command{name: "convert", usage: "convert [options] infile", description: "writes a vmw module or its json document in another format, json by default or vmw for inputs ending in .json",
+        flags: &["--base64"], values: &["-o", "--format", "--base"], run: convert}
+];
 
-extern crate byteorder;
-mod format_vmw;
-mod parser;
-mod ast;
-mod generator;
-mod vm;
-mod binary;
-mod lexer;
+const OPTIONS: &[(&str, &str)] = &[
+    ("-o", "-o file         output file, - for stdout"),
+    ("-I", "-I dir          search dir for includes"),
+    ("-D", "-D name[=value] replace the token name by value, 1 without a value"),
+    ("--format", "--format f      vmw for a module, json for its tables and code as text, or flat, ihex or srec for code with absolute addresses starting at the base"),
+    ("--base64", "--base64        json code as base64 instead of hex"),
+    ("--base", "--base address  where flat, ihex and srec code starts, org or 0 by default"),
+    ("--listing", "--listing file  write every operation with its offset and encoding"),
+    ("--map", "--map file      write the symbol map"),
+    ("-g", "-g              write the symbol map next to the output as outfile.map"),
+    ("-O", "-O              run the peephole optimizer"),
+    ("-v", "-v              print what the optimizer changed"),
+    ("--short-jumps", "--short-jumps   use relative jumps when the label is close enough"),
+    ("--lower-calls", "--lower-calls   replace call and ret for vms without them"),
+    ("--flat", "--flat          read the input as code without vmw tables, like --format flat"),
+    ("--steps", "--steps n       stop after n operations, 1000000 by default"),
+    ("--stack", "--stack         print the stack height before every operation"),
+    ("--cfg", "--cfg           print the control flow graphs as graphviz dot"),
+    ("--check", "--check         only report, exit code 1 when a file is not formatted"),
+    ("--force", "--force         build everything, even what is up to date"),
+    ("--legacy", "--legacy        read sources in the legacy dialect, see migrate"),
+    ("--watch", "--watch         run again every time the input or one of its includes changes")
+];
+
+struct Arguments {
+    positional: Vec<String>,
+    flags: Vec<String>,
+    values: Vec<(String, String)>
+}
+
+impl Arguments {
+    fn flag(&self, name: &str) -> bool {
+        return self.flags.iter().any(|flag| flag == name);
+    }
+
+    // the last value given for an option
+    fn value(&self, name: &str) -> Option<&str> {
+        return self.values.iter().rev().find(|(option, _)| option == name).map(|(_, value)| value.as_str());
+    }
+
+    fn all(&self, name: &str) -> Vec<String> {
+        return self.values.iter().filter(|(option, _)| option == name).map(|(_, value)| value.to_string()).collect();
+    }
+
+    fn options(&self) -> Options {
+        let mut options = Options::new();
+        options.optimize = self.flag("-O");
+        options.short_jumps = self.flag("--short-jumps");
+        options.lower_calls = self.flag("--lower-calls");
+        options.legacy = self.flag("--legacy");
+        options.include_dirs = self.all("-I");
+        for define in self.all("-D") {
+            match define.find('=') {
+                Some(equals) => options.defines.push((define[..equals].to_string(), define[equals+1..].to_string())),
+                None => options.defines.push((define, "1".to_string()))
+            }
+        }
+        return options;
+    }
+}
+
+// -I and -D also accept their value directly attached, like -Iasm
+fn parse_arguments(args: &[String], command: &Command) -> Result<Arguments, String> {
+    let mut arguments = Arguments{positional: Vec::new(), flags: Vec::new(), values: Vec::new()};
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
+        if command.flags.contains(&arg.as_str()) {
+            arguments.flags.push(arg.to_string());
+        } else if command.values.contains(&arg.as_str()) {
+            if i + 1 == args.len() {
+                return Err(format!("{} needs a value", arg));
+            }
+            arguments.values.push((arg.to_string(), args[i + 1].to_string()));
+            i += 1;
+        } else if (arg.starts_with("-I") || arg.starts_with("-D")) && arg.len() > 2 && command.values.contains(&&arg[..2]) {
+            arguments.values.push((arg[..2].to_string(), arg[2..].to_string()));
+        } else if arg.starts_with('-') && arg != "-" {
+            return Err(format!("unknown option {}", arg));
+        } else {
+            arguments.positional.push(arg.to_string());
+        }
+        i += 1;
+    }
+    return Ok(arguments);
+}
 
 fn print_usage() {
-    let args: Vec<String> = env::args().collect();
-    println!("Usage: {} infile outfile", args[0]);
+    println!("Usage: vmw_assembler command [options] [infile...]");
+    println!();
+    println!("Commands:");
+    for command in COMMANDS {
+        println!("    {:<12} {}", command.name, command.description);
+    }
+    println!();
+    println!("Run vmw_assembler command --help for the options of a command.");
+    println!("vmw_assembler infile outfile still works and is vmw_assembler assemble infile -o outfile.");
+}
+
+fn print_help(command: &Command) {
+    println!("Usage: vmw_assembler {}", command.usage);
+    println!();
+    println!("{}", command.description);
+    let options: Vec<&(&str, &str)> = OPTIONS.iter().filter(|(name, _)| command.flags.contains(name) || command.values.contains(name)).collect();
+    if !options.is_empty() {
+        println!();
+        println!("Options:");
+        for (_, description) in options {
+            println!("    {}", description);
+        }
+    }
+}
+
+fn report(error: &Error) -> i32 {
+    eprintln!("error: {}", error);
+    return FAILURE;
+}
+
+fn usage_error(command: &Command, message: &str) -> i32 {
+    eprintln!("error: {}", message);
+    eprintln!("Usage: vmw_assembler {}", command.usage);
+    return USAGE;
+}
+
+fn read_source(path: &str) -> Result<String, Error> {
+    return read_text(path, read_input(path)?);
+}
+
+fn read_text(path: &str, contents: Vec<u8>) -> Result<String, Error> {
+    match String::from_utf8(contents) {
+        Ok(source) => return Ok(source),
+        Err(_) => return Err(Error::Io(path.to_string(), "not valid utf-8".to_string()))
+    }
+}
+
+// --format, --base and --base64
+fn image_options(arguments: &Arguments) -> Result<Image, String> {
+    let format = arguments.value("--format").unwrap_or("vmw");
+    let mut image = match Format::from_name(format) {
+        Some(format) => Image::new(format),
+        None => return Err(format!("unknown format {}", format))
+    };
+    if let Some(base) = arguments.value("--base") {
+        match image::parse_address(base) {
+            Some(base) => image.base = Some(base),
+            None => return Err("--base needs a decimal or 0x hex address".to_string())
+        }
+    }
+    image.base64 = arguments.flag("--base64");
+    return Ok(image);
+}
+
+fn print_diagnostics(errors: &Vec<Error>, warnings: &Vec<Warning>) {
+    for warning in warnings {
+        eprintln!("warning: {}", warning);
+    }
+    for error in errors {
+        eprintln!("error: {}", error);
+    }
+}
+
+fn assemble(command: &Command, arguments: &Arguments) -> i32 {
+    if arguments.positional.len() != 1 {
+        return usage_error(command, "expected one input file");
+    }
+    let input = &arguments.positional[0];
+    if input == "-" && arguments.flag("--watch") {
+        return usage_error(command, "stdin can not be watched");
+    }
+    let image = match image_options(arguments) {
+        Ok(image) => image,
+        Err(message) => return usage_error(command, &message)
+    };
+    // next to the input with the extension of the tests, stdout when reading stdin
+    let output = match arguments.value("-o") {
+        Some(output) => output.to_string(),
+        None if input == "-" => "-".to_string(),
+        None => Path::new(input).with_extension("bin").to_string_lossy().to_string()
+    };
+    if arguments.flag("--watch") {
+        return watch(|| assemble_once(arguments, input, &output, &image));
+    }
+    return assemble_once(arguments, input, &output, &image).0;
+}
+
+// returns: exit code, files that were read
+// without a base, code starts at org
+fn assemble_once(arguments: &Arguments, input: &str, output: &str, image: &Image) -> (i32, Vec<String>) {
+    let mut files = vec![input.to_string()];
+    let source = match read_source(input) {
+        Ok(source) => source,
+        Err(error) => return (report(&error), files)
+    };
+
+    let assembly = match vmw_assembler::assemble_named(&source, if input == "-" { "" } else { input }, &arguments.options()) {
+        Ok(assembly) => assembly,
+        Err(diagnostics) => {
+            print_diagnostics(&diagnostics.errors, &diagnostics.warnings);
+            return (FAILURE, files);
+        }
+    };
+    files.extend_from_slice(&assembly.includes);
+    print_diagnostics(&Vec::new(), &assembly.warnings);
+    if arguments.flag("-v") {
+        for change in &assembly.optimizations {
+            eprintln!("{}", change);
+        }
+    }
+
+    let mut map = arguments.value("--map").map(|map| map.to_string());
+    if arguments.flag("-g") && map.is_none() && output != "-" {
+        map = Some(format!("{}.map", output));
+    }
+    match assembly.write(image, output, map.as_deref(), arguments.value("--listing"), &module_name(input)) {
+        Ok(_) => return (SUCCESS, files),
+        Err(error) => return (report(&error), files)
+    }
+}
+
+// runs once and then again every time one of the files it read changes, until interrupted
+fn watch<F: FnMut() -> (i32, Vec<String>)>(mut run: F) -> i32 {
+    watch::watch(|| {
+        let (code, files) = run();
+        return (code == SUCCESS, files);
+    }, |succeeded, files| eprintln!("{}, watching {} files for changes", if succeeded { "done" } else { "failed" }, files.len()));
+}
+
+fn disassemble(command: &Command, arguments: &Arguments) -> i32 {
+    if arguments.positional.len() != 1 {
+        return usage_error(command, "expected one input file");
+    }
+    let mut image = match image_options(arguments) {
+        Ok(image) => image,
+        Err(message) => return usage_error(command, &message)
+    };
+    if arguments.flag("--flat") {
+        image.format = Format::Flat;
+    }
+    let text = read_input(&arguments.positional[0]).and_then(|contents| vmw_assembler::disassemble(&contents, image.format, image.base.unwrap_or(0)));
+    match text.and_then(|text| write_output(arguments.value("-o").unwrap_or("-"), text.as_bytes())) {
+        Ok(_) => return SUCCESS,
+        Err(error) => return report(&error)
+    }
+}
+
+// prints the warnings of the sources among the inputs
+fn modules(arguments: &Arguments) -> Result<Vec<(String, vmw_assembler::VMW)>, i32> {
+    match load_modules(&arguments.positional, &arguments.options()) {
+        Ok((modules, warnings)) => {
+            print_diagnostics(&Vec::new(), &warnings);
+            return Ok(modules);
+        },
+        Err(diagnostics) => {
+            print_diagnostics(&diagnostics.errors, &diagnostics.warnings);
+            return Err(FAILURE);
+        }
+    }
+}
+
+fn link(command: &Command, arguments: &Arguments) -> i32 {
+    if arguments.positional.is_empty() {
+        return usage_error(command, "expected at least one input file");
+    }
+    let output = match arguments.value("-o") {
+        Some(output) => output,
+        None => return usage_error(command, "expected an output file")
+    };
+    let image = match image_options(arguments) {
+        Ok(image) => image,
+        Err(message) => return usage_error(command, &message)
+    };
+    let modules = match modules(arguments) {
+        Ok(modules) => modules,
+        Err(code) => return code
+    };
+    let bytes = linker::link(&modules).and_then(|linked| image.write(&linked, None, None, &module_name(output)));
+    match bytes.and_then(|bytes| write_output(output, &bytes)) {
+        Ok(_) => return SUCCESS,
+        Err(error) => return report(&error)
+    }
+}
+
+fn dump(command: &Command, arguments: &Arguments) -> i32 {
+    if arguments.positional.len() != 1 {
+        return usage_error(command, "expected one input file");
+    }
+    let module = match read_module(&arguments.positional[0]) {
+        Ok((module, _)) => module,
+        Err(error) => return report(&error)
+    };
+    print!("{}", module.to_text());
+    return SUCCESS;
+}
+
+fn run(command: &Command, arguments: &Arguments) -> i32 {
+    if arguments.positional.is_empty() {
+        return usage_error(command, "expected at least one input file");
+    }
+    let steps = match arguments.value("--steps").unwrap_or("1000000").parse::<u64>() {
+        Ok(steps) => steps,
+        Err(_) => return usage_error(command, "--steps needs a number")
+    };
+    let modules = match modules(arguments) {
+        Ok(modules) => modules,
+        Err(code) => return code
+    };
+    let (output, result) = vmw_assembler::run(&modules, steps);
+    io::stdout().write_all(&output).expect("could not write to stdout");
+    match result {
+        Ok(_) => return SUCCESS,
+        Err(error) => return report(&error)
+    }
+}
+
+fn check(command: &Command, arguments: &Arguments) -> i32 {
+    if arguments.positional.len() != 1 {
+        return usage_error(command, "expected one input file");
+    }
+    let input = &arguments.positional[0];
+    if input == "-" && arguments.flag("--watch") {
+        return usage_error(command, "stdin can not be watched");
+    }
+    if arguments.flag("--watch") {
+        return watch(|| check_once(arguments, input));
+    }
+    return check_once(arguments, input).0;
+}
+
+// returns: exit code, files that were read
+fn check_once(arguments: &Arguments, input: &str) -> (i32, Vec<String>) {
+    let mut files = vec![input.to_string()];
+    let (tree, diagnostics) = match read_source(input).and_then(|source| vmw_assembler::check_named(&source, if input == "-" { "" } else { input }, &arguments.options())) {
+        Ok((tree, includes, diagnostics)) => {
+            files.extend_from_slice(&includes);
+            (tree, diagnostics)
+        },
+        Err(error) => return (report(&error), files)
+    };
+    if arguments.flag("--cfg") {
+        print!("{}", cfg::to_dot(&tree, &cfg::analyse(&tree).0));
+    }
+    if arguments.flag("--stack") {
+        print!("{}", stack::to_text(&tree, &stack::analyse(&tree).0));
+    }
+    print_diagnostics(&diagnostics.errors, &diagnostics.warnings);
+    if !diagnostics.errors.is_empty() {
+        return (FAILURE, files);
+    }
+    return (SUCCESS, files);
+}
+
+fn fmt(command: &Command, arguments: &Arguments) -> i32 {
+    if arguments.flag("--check") {
+        if arguments.positional.is_empty() {
+            return usage_error(command, "expected at least one input file");
+        }
+        // lists every file that is not formatted
+        let mut code = SUCCESS;
+        for input in &arguments.positional {
+            match read_source(input).and_then(|source| formatter::format(&source).map(|formatted| formatted == source)) {
+                Ok(true) => {},
+                Ok(false) => {
+                    println!("{}: not formatted", input);
+                    code = FAILURE;
+                },
+                Err(error) => code = report(&error)
+            }
+        }
+        return code;
+    }
+    if arguments.positional.len() != 1 {
+        return usage_error(command, "expected one input file");
+    }
+    match read_source(&arguments.positional[0]).and_then(|source| formatter::format(&source)) {
+        Ok(formatted) => match write_output(arguments.value("-o").unwrap_or("-"), formatted.as_bytes()) {
+            Ok(_) => return SUCCESS,
+            Err(error) => return report(&error)
+        },
+        Err(error) => return report(&error)
+    }
+}
+
+fn build(command: &Command, arguments: &Arguments) -> i32 {
+    if arguments.positional.len() > 1 {
+        return usage_error(command, "expected at most one manifest");
+    }
+    let manifest = arguments.positional.first().map(|manifest| manifest.as_str()).unwrap_or("vmw.toml");
+    if arguments.flag("--watch") {
+        // only the first build is forced, later ones rebuild what changed
+        let mut force = arguments.flag("--force");
+        return watch(|| {
+            let result = build_once(manifest, force);
+            force = false;
+            return result;
+        });
+    }
+    return build_once(manifest, arguments.flag("--force")).0;
+}
+
+// returns: exit code, the manifest and every file the modules were built from
+fn build_once(manifest: &str, force: bool) -> (i32, Vec<String>) {
+    let mut files = vec![manifest.to_string()];
+    let project = match Project::from_file(manifest) {
+        Ok(project) => project,
+        Err(error) => return (report(&error), files)
+    };
+    let result = project.build(force);
+    files.append(&mut project.inputs());
+    match result {
+        Ok(build) => {
+            for (name, warning) in &build.warnings {
+                eprintln!("warning: {}: {}", name, warning);
+            }
+            for (name, built) in &build.steps {
+                let done = if !built { "up to date" } else if project.modules.iter().any(|module| module.name == *name) { "assembled" } else { "linked" };
+                println!("{:>10} {}", done, name);
+            }
+            return (SUCCESS, files);
+        },
+        Err((name, diagnostics)) => {
+            for warning in &diagnostics.warnings {
+                eprintln!("warning: {}: {}", name, warning);
+            }
+            for error in &diagnostics.errors {
+                eprintln!("error: {}: {}", name, error);
+            }
+            return (FAILURE, files);
+        }
+    }
+}
+
+fn lsp(command: &Command, arguments: &Arguments) -> i32 {
+    if !arguments.positional.is_empty() {
+        return usage_error(command, "expected no arguments");
+    }
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    match language_server::serve(&mut stdin.lock(), &mut stdout.lock()) {
+        Ok(true) => return SUCCESS,
+        // exit without shutdown
+        Ok(false) => return FAILURE,
+        Err(error) => return report(&error)
+    }
+}
+
+fn migrate(command: &Command, arguments: &Arguments) -> i32 {
+    if arguments.positional.len() != 1 {
+        return usage_error(command, "expected one input file");
+    }
+    let input = &arguments.positional[0];
+    let name = if input == "-" { "main".to_string() } else { module_name(input) };
+    match read_source(input).and_then(|source| vmw_assembler::legacy::translate(&source, &name)) {
+        Ok((translated, _)) => match write_output(arguments.value("-o").unwrap_or("-"), translated.as_bytes()) {
+            Ok(_) => return SUCCESS,
+            Err(error) => return report(&error)
+        },
+        Err(error) => return report(&error)
+    }
+}
+
+fn convert(command: &Command, arguments: &Arguments) -> i32 {
+    if arguments.positional.len() != 1 {
+        return usage_error(command, "expected one input file");
+    }
+    let mut image = match image_options(arguments) {
+        Ok(image) => image,
+        Err(message) => return usage_error(command, &message)
+    };
+    let input = &arguments.positional[0];
+    if arguments.value("--format").is_none() {
+        image.format = if input.ends_with(".json") { Format::Vmw } else { Format::Json };
+    }
+    let output = arguments.value("-o").unwrap_or("-");
+    let bytes = read_module(input).and_then(|(module, map)| image.write(&module, map.as_ref(), None, &module_name(input)));
+    match bytes.and_then(|bytes| write_output(output, &bytes)) {
+        Ok(_) => return SUCCESS,
+        Err(error) => return report(&error)
+    }
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 3 {
+    if args.len() < 2 {
         print_usage();
+        process::exit(USAGE);
+    }
+    if args[1] == "help" || args[1] == "--help" || args[1] == "-h" {
+        match args.get(2).and_then(|name| COMMANDS.iter().find(|command| command.name == name)) {
+            Some(command) => print_help(command),
+            None => print_usage()
+        }
         return;
     }
 
-    let file: &String = &args[1];
-    let mut f = File::open(file).expect("file not found");
-
-    let mut contents = String::new();
-    f.read_to_string(&mut contents)
-        .expect("something went wrong reading the file");
-
-    let tokens = lexer::lex(&contents);
-    let ast = parser::parse(&tokens);
+    // the command line from before there were commands
+    if args.len() == 3 && !args[1].starts_with('-') && !COMMANDS.iter().any(|command| command.name == args[1]) {
+        let command = COMMANDS.iter().find(|command| command.name == "assemble").unwrap();
+        let code = match parse_arguments(&[args[1].to_string(), "-o".to_string(), args[2].to_string()], command) {
+            Ok(arguments) => (command.run)(command, &arguments),
+            Err(message) => usage_error(command, &message)
+        };
+        process::exit(code);
+    }
 
-    let vmw: format_vmw::VMW = generator::generate(&ast);
-    vmw.to_file(&args[2]);
+    let command = match COMMANDS.iter().find(|command| command.name == args[1]) {
+        Some(command) => command,
+        None => {
+            eprintln!("error: unknown command {}", args[1]);
+            print_usage();
+            process::exit(USAGE);
+        }
+    };
+    if args[2..].iter().any(|arg| arg == "--help" || arg == "-h") {
+        print_help(command);
+        return;
+    }
+    let code = match parse_arguments(&args[2..], command) {
+        Ok(arguments) => (command.run)(command, &arguments),
+        Err(message) => usage_error(command, &message)
+    };
+    process::exit(code);
 }
\ No newline at end of file
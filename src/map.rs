@@ -0,0 +1,113 @@
+use std::fs::File;
+use std::io::prelude::*;
+use json::Json;
+use error::Error;
+
+// the language has no data or constant directives, a module is only procedures and their labels
+// defines come from the command line and are replaced in the text before parsing, so they never get an address
+pub enum SymbolKind {
+    Procedure,
+    Label,
+    External
+}
+
+impl SymbolKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SymbolKind::Procedure => return "proc",
+            SymbolKind::Label => return "label",
+            SymbolKind::External => return "extern"
+        }
+    }
+}
+
+pub enum Visibility {
+    Global,
+    Local,
+    Import
+}
+
+impl Visibility {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Visibility::Global => return "global",
+            Visibility::Local => return "local",
+            Visibility::Import => return "import"
+        }
+    }
+}
+
+pub struct Symbol {
+    pub kind: SymbolKind,
+    pub name: String,
+    pub offset: u64,
+    pub size: u64,
+    pub visibility: Visibility
+}
+
+// every symbol of an assembled module, in the order it appears in the binary
+#[derive(Default)]
+pub struct SymbolMap {
+    pub symbols: Vec<Symbol>
+}
+
+impl SymbolMap {
+    pub fn new() -> SymbolMap {
+        return SymbolMap{symbols: Vec::new()};
+    }
+
+    // one symbol per line: kind name offset size visibility
+    // offsets are relative to the start of the binary section
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        text.push_str("# vmw symbol map v1\n");
+        text.push_str("# kind name offset size visibility\n");
+        for symbol in &self.symbols {
+            text.push_str(&format!("{} {} {:#018x} {:#x} {}\n", symbol.kind.name(), symbol.name, symbol.offset, symbol.size, symbol.visibility.name()));
+        }
+        return text;
+    }
+
+    // an array of {"kind", "name", "offset", "size", "visibility"} with the names of to_text
+    pub fn to_json(&self) -> Json {
+        return Json::Array(self.symbols.iter().map(|symbol| Json::object(vec![
+            ("kind", Json::from_text(symbol.kind.name())),
+            ("name", Json::from_text(&symbol.name)),
+            ("offset", Json::from_u64(symbol.offset)),
+            ("size", Json::from_u64(symbol.size)),
+            ("visibility", Json::from_text(symbol.visibility.name()))
+        ])).collect());
+    }
+
+    pub fn from_json(json: &Json) -> Result<SymbolMap, Error> {
+        let invalid = |what: &str| Error::InvalidModule(format!("symbol {} is missing or invalid", what));
+        let mut symbols: Vec<Symbol> = Vec::new();
+        for symbol in json.as_array().ok_or(invalid("list"))? {
+            let kind = match symbol.get("kind").and_then(Json::as_str) {
+                Some("proc") => SymbolKind::Procedure,
+                Some("label") => SymbolKind::Label,
+                Some("extern") => SymbolKind::External,
+                _ => return Err(invalid("kind"))
+            };
+            let visibility = match symbol.get("visibility").and_then(Json::as_str) {
+                Some("global") => Visibility::Global,
+                Some("local") => Visibility::Local,
+                Some("import") => Visibility::Import,
+                _ => return Err(invalid("visibility"))
+            };
+            symbols.push(Symbol{
+                kind: kind,
+                name: symbol.get("name").and_then(Json::as_str).ok_or(invalid("name"))?.to_string(),
+                offset: symbol.get("offset").and_then(Json::as_u64).ok_or(invalid("offset"))?,
+                size: symbol.get("size").and_then(Json::as_u64).ok_or(invalid("size"))?,
+                visibility: visibility
+            });
+        }
+        return Ok(SymbolMap{symbols: symbols});
+    }
+
+    pub fn to_file(&self, path: &str) -> Result<(), Error> {
+        return File::create(path).and_then(|mut f| f.write_all(self.to_text().as_bytes()))
+            .map_err(|error| Error::Io(path.to_string(), error.to_string()));
+    }
+}
@@ -0,0 +1,243 @@
+use ast::{Tree, Procedure, Operation, Address};
+use vm::Opcode;
+
+enum Item {
+    Label(String),
+    Operation(Operation)
+}
+
+// labels and operations in source order, so rewrites do not have to keep label indices up to date
+fn to_items(procedure: &Procedure) -> Vec<Item> {
+    let mut items: Vec<Item> = Vec::new();
+    let mut next_label: usize = 0;
+    for op_index in 0..(procedure.operations.len() + 1) {
+        while next_label < procedure.labels.len() && procedure.labels[next_label].1 == op_index {
+            items.push(Item::Label(procedure.labels[next_label].0.to_string()));
+            next_label += 1;
+        }
+        if op_index < procedure.operations.len() {
+            items.push(Item::Operation(procedure.operations[op_index].clone()));
+        }
+    }
+    return items;
+}
+
+fn from_items(items: Vec<Item>, procedure: &mut Procedure) {
+    procedure.labels.clear();
+    procedure.operations.clear();
+    for item in items {
+        match item {
+            Item::Label(label) => procedure.labels.push((label, procedure.operations.len())),
+            Item::Operation(operation) => procedure.operations.push(operation)
+        }
+    }
+}
+
+fn operation(items: &[Item], index: usize) -> Option<&Operation> {
+    match items.get(index) {
+        Some(Item::Operation(operation)) => Some(operation),
+        _ => None
+    }
+}
+
+fn literal(operation: &Operation) -> u64 {
+    match operation.operands[0] {
+        Address::IntLiteral(value) => value,
+        _ => 0
+    }
+}
+
+fn matching_pop(opcode: Opcode) -> Option<Opcode> {
+    match opcode {
+        Opcode::PushU8 => Some(Opcode::PopU8),
+        Opcode::PushU16 => Some(Opcode::PopU16),
+        Opcode::PushU32 => Some(Opcode::PopU32),
+        Opcode::PushU64 => Some(Opcode::PopU64),
+        _ => None
+    }
+}
+
+// index of the first operation after a label, skipping any labels in between
+fn label_target(items: &[Item], label: &str) -> Option<usize> {
+    let position = items.iter().position(|item| match item {
+        Item::Label(name) => name == label,
+        _ => false
+    })?;
+    return (position..items.len()).find(|index| operation(items, *index).is_some());
+}
+
+// push immediately followed by a pop of the same width
+fn drop_push_pop(items: &mut Vec<Item>, changes: &mut Vec<String>) -> bool {
+    for i in 0..items.len() {
+        let removed = match (operation(items, i), operation(items, i + 1)) {
+            (Some(push), Some(pop)) if matching_pop(push.opcode) == Some(pop.opcode) => format!("removed {} followed by {}", push, pop),
+            _ => continue
+        };
+        changes.push(removed);
+        items.drain(i..i + 2);
+        return true;
+    }
+    return false;
+}
+
+// jmp to a label that directly follows it
+fn fold_jmp_to_next(items: &mut Vec<Item>, changes: &mut Vec<String>) -> bool {
+    for i in 0..items.len() {
+        let folded = match operation(items, i) {
+            Some(jmp) if jmp.opcode == Opcode::Jmp => match &jmp.operands[0] {
+                Address::Label(label) => {
+                    let mut next = i + 1;
+                    let mut found = false;
+                    while let Some(Item::Label(name)) = items.get(next) {
+                        found = found || name == label;
+                        next += 1;
+                    }
+                    if found { format!("removed {} to the next operation", jmp) } else { continue }
+                },
+                _ => continue
+            },
+            _ => continue
+        };
+        changes.push(folded);
+        items.remove(i);
+        return true;
+    }
+    return false;
+}
+
+// jumps to a label whose operation is itself a jmp go to the final target directly
+// takes a Vec like the rules that remove items, they all are a Rule
+#[allow(clippy::ptr_arg)]
+fn thread_jumps(items: &mut Vec<Item>, changes: &mut Vec<String>) -> bool {
+    for i in 0..items.len() {
+        let (opcode, label) = match operation(items, i) {
+            Some(jump) if jump.opcode == Opcode::Jmp || jump.opcode == Opcode::JmpTrue || jump.opcode == Opcode::JmpFalse => match &jump.operands[0] {
+                Address::Label(label) => (jump.opcode, label.to_string()),
+                _ => continue
+            },
+            _ => continue
+        };
+        // follow the chain of jmps to its end, leaving loops made only of jmps alone
+        let mut visited: Vec<String> = vec![label.to_string()];
+        let mut target = Address::Label(label.to_string());
+        let mut looped = false;
+        while let Address::Label(current) = target.clone() {
+            match label_target(items, &current).and_then(|index| operation(items, index)) {
+                Some(next) if next.opcode == Opcode::Jmp => {
+                    match &next.operands[0] {
+                        Address::Label(next_label) if visited.contains(next_label) => {
+                            looped = true;
+                            break;
+                        },
+                        Address::Label(next_label) => visited.push(next_label.to_string()),
+                        _ => {}
+                    }
+                    target = next.operands[0].clone();
+                },
+                _ => break
+            }
+        }
+        if looped || target == Address::Label(label.to_string()) {
+            continue;
+        }
+        changes.push(format!("{} &{} now jumps to {}", opcode.instruction().mnemonic, label, target));
+        items[i] = Item::Operation(Operation{opcode: opcode, operands: vec![target]});
+        return true;
+    }
+    return false;
+}
+
+// consecutive spi and spd become a single one, or nothing when they cancel out
+fn merge_stack_pointer(items: &mut Vec<Item>, changes: &mut Vec<String>) -> bool {
+    // literals beyond i64 and sums that overflow are left alone
+    let signed = |operation: &Operation| -> Option<i64> {
+        match operation.opcode {
+            Opcode::Spi if literal(operation) <= i64::MAX as u64 => Some(literal(operation) as i64),
+            Opcode::Spd if literal(operation) <= i64::MAX as u64 => Some(-(literal(operation) as i64)),
+            _ => None
+        }
+    };
+    for i in 0..items.len() {
+        let (first, second, total) = match (operation(items, i), operation(items, i + 1)) {
+            (Some(first), Some(second)) => match (signed(first), signed(second)) {
+                (Some(a), Some(b)) => match a.checked_add(b) {
+                    Some(total) => (first.to_string(), second.to_string(), total),
+                    None => continue
+                },
+                _ => continue
+            },
+            _ => continue
+        };
+        items.drain(i..i + 2);
+        if total == 0 {
+            changes.push(format!("removed {} followed by {}", first, second));
+        } else {
+            let opcode = if total > 0 { Opcode::Spi } else { Opcode::Spd };
+            let merged = Operation{opcode: opcode, operands: vec![Address::IntLiteral(total.unsigned_abs())]};
+            changes.push(format!("merged {} and {} into {}", first, second, merged));
+            items.insert(i, Item::Operation(merged));
+        }
+        return true;
+    }
+    return false;
+}
+
+// operations after an unconditional jump can only run when they are labelled
+fn remove_dead_code(items: &mut Vec<Item>, changes: &mut Vec<String>) -> bool {
+    for i in 0..items.len() {
+        match operation(items, i) {
+            Some(jump) if jump.opcode == Opcode::Jmp || jump.opcode == Opcode::JmpRel8 || jump.opcode == Opcode::JmpRel16 ||
+                jump.opcode == Opcode::Jmps || jump.opcode == Opcode::Ret || jump.opcode == Opcode::Halt => {},
+            _ => continue
+        }
+        let dead = match operation(items, i + 1) {
+            Some(dead) => format!("removed unreachable {}", dead),
+            None => continue
+        };
+        changes.push(dead);
+        items.remove(i + 1);
+        return true;
+    }
+    return false;
+}
+
+// index of the first jump or call to a literal address or displacement, every rule moves or resizes
+// operations by their labels only, so such a target would silently land somewhere else
+fn literal_jump(procedure: &Procedure) -> Option<usize> {
+    return procedure.operations.iter().position(|operation| {
+        matches!(operation.operands.first(), Some(Address::IntLiteral(_))) && matches!(operation.opcode,
+            Opcode::Jmp | Opcode::JmpRel8 | Opcode::JmpRel16 | Opcode::JmpTrue | Opcode::JmpTrueRel8 | Opcode::JmpTrueRel16 |
+            Opcode::JmpFalse | Opcode::JmpFalseRel8 | Opcode::JmpFalseRel16 | Opcode::Call)
+    });
+}
+
+// rewrites items in place, returns whether it changed anything
+type Rule = fn(&mut Vec<Item>, &mut Vec<String>) -> bool;
+
+// rewrites every procedure until none of the rules applies anymore
+// returns: a description of every change, prefixed with its procedure
+pub fn optimize(source: &mut Tree) -> Vec<String> {
+    let rules: [Rule; 5] = [
+        drop_push_pop,
+        fold_jmp_to_next,
+        thread_jumps,
+        merge_stack_pointer,
+        remove_dead_code
+    ];
+
+    let mut changes: Vec<String> = Vec::new();
+    for (name, procedure) in source.procedures.iter_mut() {
+        if let Some(op_index) = literal_jump(procedure) {
+            changes.push(format!("proc {}: left unoptimized, operation {} jumps to a literal address", name, op_index));
+            continue;
+        }
+        let mut items = to_items(procedure);
+        let mut procedure_changes: Vec<String> = Vec::new();
+        while rules.iter().any(|rule| rule(&mut items, &mut procedure_changes)) {}
+        for change in procedure_changes {
+            changes.push(format!("proc {}: {}", name, change));
+        }
+        from_items(items, procedure);
+    }
+    return changes;
+}
\ No newline at end of file
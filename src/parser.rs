@@ -1,22 +1,43 @@
 use ast::*;
-use lexer::{Token, Opcode};
+use lexer::Token;
+use vm::{Opcode, Type};
+use error::Error;
 use std;
 
-pub fn parse(source: &Vec<Token>) -> Tree {
+pub fn parse(source: &Vec<Token>) -> Result<Tree, Error> {
     let mut procedures: Vec<(String, Procedure)> = Vec::new();
+    let mut origin: Option<u64> = None;
 
-   let mut source_leftover = source.as_slice();
-    while std::mem::discriminant(&source_leftover[0]) != std::mem::discriminant(&Token::EOF) {
+    let mut source_leftover = source.as_slice();
+    loop {
+        // newlines after the last end proc
+        while std::mem::discriminant(&source_leftover[0]) == std::mem::discriminant(&Token::NewLine) {
+            source_leftover = &source_leftover[1..];
+        }
+        if std::mem::discriminant(&source_leftover[0]) == std::mem::discriminant(&Token::EOF) {
+            break;
+        }
+        if std::mem::discriminant(&source_leftover[0]) == std::mem::discriminant(&Token::Org) {
+            if !procedures.is_empty() || origin.is_some() {
+                return Err(Error::InvalidOrigin("org must come once, before the first procedure".to_string()));
+            }
+            match (&source_leftover[1], source_leftover.get(2)) {
+                (Token::IntLiteral(address), Some(Token::NewLine)) | (Token::IntLiteral(address), Some(Token::EOF)) => origin = Some(*address),
+                _ => return Err(Error::InvalidOrigin("org needs an address on its own line".to_string()))
+            }
+            source_leftover = &source_leftover[2..];
+            continue;
+        }
         match parse_proc(source_leftover) {
             Some((name, procedure, leftover)) => {
                 procedures.push((name, procedure));
                 source_leftover = leftover;
             },
-            None => panic!("Invalid procedure")
+            None => return Err(Error::InvalidProcedure(procedures.len() + 1))
         }
     }
 
-    return Tree{procedures: procedures};
+    return Ok(Tree{procedures: procedures, origin: origin});
 }
 
 enum Rule {
@@ -24,265 +45,80 @@ enum Rule {
     Label(String)
 }
 
-fn parse_cmp_u8(source: &[Token]) -> Option<(Rule, &[Token])> {
-    return Some((Rule::Operation(Operation::CmpU8), source));
-}
-
-fn parse_cpg_u8(source: &[Token]) -> Option<(Rule, &[Token])> {
-    match &source[0] {
-        Token::IntLiteral(value) => {
-            let op = Operation::CpgU8(CpgU8{address: Address::IntLiteral(*value)});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::LabelRef(value) => {
-            let op = Operation::CpgU8(CpgU8{address: Address::Label(value.to_string())});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::ProcRef(value) => {
-            let op = Operation::CpgU8(CpgU8{address: Address::ProcRef(value.to_string())});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::ExtProcRef(value) => {
-            let op = Operation::CpgU8(CpgU8{address: Address::ExtProcRef(ExtProcRef{module: value.module.to_string(), procedure: value.procedure.to_string() })});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        _ => {
-            println!("parse_cpg_u8");
-            return None;
-        }
-    }
-}
-
-fn parse_cpl_u8(source: &[Token]) -> Option<(Rule, &[Token])> {
-    match &source[0] {
-        Token::IntLiteral(value) => {
-            let op = Operation::CplU8(CplU8{address: Address::IntLiteral(*value)});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::LabelRef(value) => {
-            let op = Operation::CplU8(CplU8{address: Address::Label(value.to_string())});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::ProcRef(value) => {
-            let op = Operation::CplU8(CplU8{address: Address::ProcRef(value.to_string())});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::ExtProcRef(value) => {
-            let op = Operation::CplU8(CplU8{address: Address::ExtProcRef(ExtProcRef{module: value.module.to_string(), procedure: value.procedure.to_string() })});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        _ => {
-            println!("parse_cpl_u8");
-            return None;
-        }
-    }
-}
-
-fn parse_halt(source: &[Token]) -> Option<(Rule, &[Token])> {
-    return Some((Rule::Operation(Operation::Halt), source));
-}
-
-fn parse_jmp(source: &[Token]) -> Option<(Rule, &[Token])> {
-    match &source[0] {
-        Token::IntLiteral(value) => {
-            let op = Operation::Jmp(Jmp{address: Address::IntLiteral(*value)});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::LabelRef(value) => {
-            let op = Operation::Jmp(Jmp{address: Address::Label(value.to_string())});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::ProcRef(value) => {
-            let op = Operation::Jmp(Jmp{address: Address::ProcRef(value.to_string())});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::ExtProcRef(value) => {
-            let op = Operation::Jmp(Jmp{address: Address::ExtProcRef(ExtProcRef{module: value.module.to_string(), procedure: value.procedure.to_string() })});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        _ => {
-            println!("parse_jmp");
-            return None;
-        }
-    }
-}
-
-fn parse_jmps(source: &[Token]) -> Option<(Rule, &[Token])> {
-    return Some((Rule::Operation(Operation::Jmps), source));
-}
-
-fn parse_jmp_true(source: &[Token]) -> Option<(Rule, &[Token])> {
-    match &source[0] {
-        Token::IntLiteral(value) => {
-            let op = Operation::JmpTrue(JmpTrue{address: Address::IntLiteral(*value)});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::LabelRef(value) => {
-            let op = Operation::JmpTrue(JmpTrue{address: Address::Label(value.to_string())});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::ProcRef(value) => {
-            let op = Operation::JmpTrue(JmpTrue{address: Address::ProcRef(value.to_string())});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::ExtProcRef(value) => {
-            let op = Operation::JmpTrue(JmpTrue{address: Address::ExtProcRef(ExtProcRef{module: value.module.to_string(), procedure: value.procedure.to_string() })});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        _ => {
-            println!("parse_jmp_true");
-            return None;
-        }
-    }
-}
-
-fn parse_pop_u8(source: &[Token]) -> Option<(Rule, &[Token])> {
-    return Some((Rule::Operation(Operation::PopU8), source));
-}
-
-fn parse_push_u64(source: &[Token]) -> Option<(Rule, &[Token])> {
-    match &source[0] {
-        Token::IntLiteral(value) => {
-            let op = Operation::PushU64(PushU64{address: Address::IntLiteral(*value)});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::LabelRef(value) => {
-            let op = Operation::PushU64(PushU64{address: Address::Label(value.to_string())});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::ProcRef(value) => {
-            let op = Operation::PushU64(PushU64{address: Address::ProcRef(value.to_string())});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::ExtProcRef(value) => {
-            let op = Operation::PushU64(PushU64{address: Address::ExtProcRef(ExtProcRef{module: value.module.to_string(), procedure: value.procedure.to_string() })});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        _ => {
-            println!("parse_push_u64");
-            return None;
-        }
+fn parse_address(token: &Token) -> Option<Address> {
+    match token {
+        Token::IntLiteral(value) => Some(Address::IntLiteral(*value)),
+        Token::LabelRef(value) => Some(Address::Label(value.to_string())),
+        Token::ProcRef(value) => Some(Address::ProcRef(value.to_string())),
+        Token::ExtProcRef(value) => Some(Address::ExtProcRef(ExtProcRef{module: value.module.to_string(), procedure: value.procedure.to_string()})),
+        _ => None
     }
 }
 
-fn parse_push_u8(source: &[Token]) -> Option<(Rule, &[Token])> {
-    match &source[0] {
-        Token::IntLiteral(value) => {
-            let op = Operation::PushU8(PushU8{value: *value as u8});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        _ => {
-            println!("parse_push_u8");
-            return None;
+// an opcode followed by one token for every operand of its instruction
+fn parse_operation(opcode: Opcode, source: &[Token]) -> Option<(Rule, &[Token])> {
+    let instruction = opcode.instruction();
+    let mut operands: Vec<Address> = Vec::new();
+    for token in source.get(..instruction.operands.len())? {
+        match parse_address(token) {
+            Some(address) => operands.push(address),
+            None => return None
         }
     }
+    let op = Operation{opcode: opcode, operands: operands};
+    return Some((Rule::Operation(op), &source[instruction.operands.len()..]));
 }
 
-fn parse_set_u8(source: &[Token]) -> Option<(Rule, &[Token])> {
+fn parse_rule(source: &[Token]) -> Option<(Rule, &[Token])> {
     match &source[0] {
-        Token::IntLiteral(value) => {
-            let op = Operation::SetU8(SetU8{address: Address::IntLiteral(*value)});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::LabelRef(value) => {
-            let op = Operation::SetU8(SetU8{address: Address::Label(value.to_string())});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        Token::ProcRef(value) => {
-            let op = Operation::SetU8(SetU8{address: Address::ProcRef(value.to_string())});
-            return Some((Rule::Operation(op), &source[1..]));
+        Token::Opcode(opcode) => {
+            return parse_operation(*opcode, &source[1..]);
         },
-        Token::ExtProcRef(value) => {
-            let op = Operation::SetU8(SetU8{address: Address::ExtProcRef(ExtProcRef{module: value.module.to_string(), procedure: value.procedure.to_string() })});
-            return Some((Rule::Operation(op), &source[1..]));
+        Token::Label(label) => {
+            return Some((Rule::Label(label.to_string()), &source[1..]));
         },
         _ => {
-            println!("parse_set_u8");
             return None;
         }
     }
 }
 
-fn parse_spd(source: &[Token]) -> Option<(Rule, &[Token])> {
+// (type, ...)
+fn parse_types(source: &[Token]) -> Option<(Vec<Type>, &[Token])> {
+    let mut types: Vec<Type> = Vec::new();
     match &source[0] {
-        Token::IntLiteral(value) => {
-            let op = Operation::Spd(Spd{value: *value});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        _ => {
-            println!("parse_spd");
-            return None;
-        }
+        Token::ParenOpen => {},
+        _ => return None
     }
-}
-
-fn parse_spi(source: &[Token]) -> Option<(Rule, &[Token])> {
-    match &source[0] {
-        Token::IntLiteral(value) => {
-            let op = Operation::Spi(Spi{value: *value});
-            return Some((Rule::Operation(op), &source[1..]));
-        },
-        _ => {
-            println!("parse_spi");
-            return None;
+    let mut source_leftover = &source[1..];
+    loop {
+        match &source_leftover[0] {
+            Token::ParenClose => return Some((types, &source_leftover[1..])),
+            Token::Type(t) if types.is_empty() => {
+                types.push(*t);
+                source_leftover = &source_leftover[1..];
+            },
+            Token::Comma if !types.is_empty() => {
+                match &source_leftover[1] {
+                    Token::Type(t) => types.push(*t),
+                    _ => return None
+                }
+                source_leftover = &source_leftover[2..];
+            },
+            _ => return None
         }
     }
 }
 
-
-fn parse_rule(source: &[Token]) -> Option<(Rule, &[Token])> {
-    match &source[0] {
-        Token::Opcode(opcode) => {
-            match opcode {
-                Opcode::CmpU8 => {
-                    return parse_cmp_u8(&source[1..]);
-                },
-                Opcode::CpgU8 => {
-                    return parse_cpg_u8(&source[1..]);
-                },
-                Opcode::CplU8 => {
-                    return parse_cpl_u8(&source[1..]);
-                },
-                Opcode::Halt => {
-                    return parse_halt(&source[1..]);
-                },
-                Opcode::Jmp => {
-                    return parse_jmp(&source[1..]);
-                },
-                Opcode::Jmps => {
-                    return parse_jmps(&source[1..]);
-                },
-                Opcode::JmpTrue => {
-                    return parse_jmp_true(&source[1..]);
-                },
-                Opcode::PopU8 => {
-                    return parse_pop_u8(&source[1..]);
-                },
-                Opcode::PushU64 => {
-                    return parse_push_u64(&source[1..]);
-                },
-                Opcode::PushU8 => {
-                    return parse_push_u8(&source[1..]);
-                },
-                Opcode::SetU8 => {
-                    return parse_set_u8(&source[1..]);
-                },
-                Opcode::Spd => {
-                    return parse_spd(&source[1..]);
-                },
-                Opcode::Spi => {
-                    return parse_spi(&source[1..]);
-                }
-            }
-        },
-        Token::Label(label) => {
-            return Some((Rule::Label(label.to_string()), &source[1..]));
-        },
-        _ => {
-            println!("nothing");
-            return None;
-        }
+// (parameters) -> (returns)
+fn parse_signature(source: &[Token]) -> Option<(Signature, &[Token])> {
+    let (parameters, source_leftover) = parse_types(source)?;
+    match &source_leftover[0] {
+        Token::Arrow => {},
+        _ => return None
     }
+    let (returns, source_leftover) = parse_types(&source_leftover[1..])?;
+    return Some((Signature{parameters: parameters, returns: returns}, source_leftover));
 }
 
 fn parse_proc(source: &[Token]) -> Option<(String, Procedure, &[Token])> {
@@ -298,13 +134,24 @@ fn parse_proc(source: &[Token]) -> Option<(String, Procedure, &[Token])> {
         _ => return None
     }
     let name: String;
+    let mut signature: Option<Signature> = None;
     match &source_leftover[1] {
         Token::Label(label) => {
             name = label.to_string();
+            source_leftover = &source_leftover[2..];
+        },
+        Token::Identifier(identifier) => {
+            name = identifier.to_string();
+            match parse_signature(&source_leftover[2..]) {
+                Some((parsed, leftover)) => {
+                    signature = Some(parsed);
+                    source_leftover = leftover;
+                },
+                None => return None
+            }
         },
         _ => return None
     }
-    source_leftover = &source_leftover[2..];
 
     // parse all rules
     let mut labels: Vec<(String, usize)> = Vec::new();
@@ -340,5 +187,5 @@ fn parse_proc(source: &[Token]) -> Option<(String, Procedure, &[Token])> {
     }
     source_leftover = &source_leftover[2..];
 
-    return Some((name, Procedure{labels: labels, operations: operations}, source_leftover));
+    return Some((name, Procedure{labels: labels, operations: operations, signature: signature}, source_leftover));
 }
\ No newline at end of file
@@ -0,0 +1,99 @@
+use std::path::Path;
+use error::Error;
+use resolver::Resolver;
+
+// includes nested deeper than this are refused, whatever the paths look like
+const MAX_INCLUDE_DEPTH: usize = 64;
+
+// include "path" on a line of its own is replaced by the contents of that file,
+// looked up next to the including file first and then in every include directory, through resolver
+// afterwards every token that is exactly the name of a define is replaced by its value
+// returns: the preprocessed source, paths of every included file
+pub fn preprocess(source: &str, path: &str, include_dirs: &[String], defines: &[(String, String)], resolver: &dyn Resolver) -> Result<(String, Vec<String>), Error> {
+    let mut included: Vec<String> = Vec::new();
+    let mut stack: Vec<String> = vec![resolver.canonical(path)];
+    let expanded = expand(source, path, include_dirs, resolver, &mut stack, &mut included)?;
+    return Ok((replace_defines(&expanded, defines), included));
+}
+
+fn include_path(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if !line.starts_with("include ") {
+        return None;
+    }
+    let quoted = line["include ".len()..].trim();
+    if quoted.len() < 2 || !quoted.starts_with('"') || !quoted.ends_with('"') {
+        return None;
+    }
+    return Some(&quoted[1..quoted.len()-1]);
+}
+
+fn resolve(name: &str, including: &str, include_dirs: &[String], resolver: &dyn Resolver) -> Option<String> {
+    let mut candidates: Vec<String> = Vec::new();
+    match Path::new(including).parent() {
+        Some(dir) => candidates.push(dir.join(name).to_string_lossy().to_string()),
+        None => candidates.push(name.to_string())
+    }
+    for dir in include_dirs {
+        candidates.push(Path::new(dir).join(name).to_string_lossy().to_string());
+    }
+    return candidates.into_iter().find(|candidate| resolver.exists(candidate));
+}
+
+fn expand(source: &str, path: &str, include_dirs: &[String], resolver: &dyn Resolver, stack: &mut Vec<String>, included: &mut Vec<String>) -> Result<String, Error> {
+    let mut result = String::new();
+    for line in source.lines() {
+        match include_path(line) {
+            Some(name) => {
+                let file = match resolve(name, path, include_dirs, resolver) {
+                    Some(file) => file,
+                    None => return Err(Error::Include(name.to_string(), path.to_string()))
+                };
+                let canonical = resolver.canonical(&file);
+                if stack.contains(&canonical) {
+                    return Err(Error::IncludeCycle(file));
+                }
+                if stack.len() > MAX_INCLUDE_DEPTH {
+                    return Err(Error::IncludeDepth(file));
+                }
+                let contents = resolver.read(&file)?;
+                if !included.contains(&file) {
+                    included.push(file.to_string());
+                }
+                stack.push(canonical);
+                let expanded = expand(&contents, &file, include_dirs, resolver, stack, included)?;
+                stack.pop();
+                result.push_str(&expanded);
+                result.push('\n');
+            },
+            None => {
+                result.push_str(line);
+                result.push('\n');
+            }
+        }
+    }
+    return Ok(result);
+}
+
+fn replace_defines(source: &str, defines: &[(String, String)]) -> String {
+    if defines.is_empty() {
+        return source.to_string();
+    }
+    let mut result = String::new();
+    let mut token = String::new();
+    // the lexer splits tokens on whitespace and the punctuation of signatures
+    for c in source.chars().chain("\n".chars()) {
+        if c == ' ' || c == '\t' || c == '\n' || c == '\r' || c == '(' || c == ')' || c == ',' {
+            match defines.iter().rev().find(|(name, _)| *name == token) {
+                Some((_, value)) => result.push_str(value),
+                None => result.push_str(&token)
+            }
+            token.clear();
+            result.push(c);
+        } else {
+            token.push(c);
+        }
+    }
+    result.pop();
+    return result;
+}
\ No newline at end of file
@@ -0,0 +1,411 @@
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use error::{Error, Warning};
+use format_vmw::VMW;
+use linker;
+use {Options, Diagnostics, read_file, assemble_named, module_name};
+
+// a project manifest is a small subset of toml, paths are relative to the manifest:
+//
+//     include = ["asm/console"]
+//     build = "build"
+//
+//     [defines]
+//     letter = "0x41"
+//
+//     [[module]]
+//     source = "asm/printcstr.asm"
+//     optimize = true
+//
+//     [[link]]
+//     output = "build/printcstr.bin"
+//     modules = ["printcstr", "printc"]
+pub struct Module {
+    // how other modules call it, the file name of the source by default
+    pub name: String,
+    pub source: String,
+    pub optimize: bool,
+    pub short_jumps: bool
+}
+
+pub struct Link {
+    pub output: String,
+    pub modules: Vec<String>
+}
+
+pub struct Project {
+    // directory of the manifest, every other path is relative to it
+    pub dir: String,
+    // assembled modules go here as name.bin, together with the build state
+    pub build_dir: String,
+    pub include_dirs: Vec<String>,
+    pub defines: Vec<(String, String)>,
+    pub modules: Vec<Module>,
+    pub links: Vec<Link>
+}
+
+// what a build did
+pub struct Build {
+    // module name or link output, whether it had to be built
+    pub steps: Vec<(String, bool)>,
+    // module name, warning
+    pub warnings: Vec<(String, Warning)>
+}
+
+const STATE_FILE: &str = ".vmw-build";
+
+enum Value {
+    Text(String),
+    List(Vec<String>),
+    Bool(bool)
+}
+
+fn manifest_error<T>(line: usize, description: &str) -> Result<T, Error> {
+    return Err(Error::Manifest(line, description.to_string()));
+}
+
+fn parse_string(text: &str, line: usize) -> Result<String, Error> {
+    if text.len() < 2 || !text.starts_with('"') || !text.ends_with('"') || text[1..text.len()-1].contains('"') {
+        return manifest_error(line, &format!("expected a quoted string instead of {}", text));
+    }
+    return Ok(text[1..text.len()-1].to_string());
+}
+
+fn parse_value(text: &str, line: usize) -> Result<Value, Error> {
+    if text == "true" || text == "false" {
+        return Ok(Value::Bool(text == "true"));
+    }
+    if text.starts_with('[') {
+        if !text.ends_with(']') {
+            return manifest_error(line, "list is not closed");
+        }
+        let mut items: Vec<String> = Vec::new();
+        for item in text[1..text.len()-1].split(',').map(|item| item.trim()).filter(|item| !item.is_empty()) {
+            items.push(parse_string(item, line)?);
+        }
+        return Ok(Value::List(items));
+    }
+    return Ok(Value::Text(parse_string(text, line)?));
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    return line;
+}
+
+fn join(dir: &str, path: &str) -> String {
+    return Path::new(dir).join(path).to_string_lossy().to_string();
+}
+
+// fnv-1a, stable between runs and versions unlike the hasher of the standard library
+fn hash(hash: u64, bytes: &[u8]) -> u64 {
+    let mut hash = hash;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    return hash;
+}
+
+impl Project {
+    pub fn parse(text: &str, dir: &str) -> Result<Project, Error> {
+        let mut project = Project{dir: dir.to_string(), build_dir: join(dir, "build"), include_dirs: Vec::new(), defines: Vec::new(), modules: Vec::new(), links: Vec::new()};
+        let mut section = String::new();
+        // line of the [[module]] and [[link]] tables, for errors about them
+        let mut module_lines: Vec<usize> = Vec::new();
+        let mut link_lines: Vec<usize> = Vec::new();
+        for (line_index, line) in text.lines().enumerate() {
+            let line_number = line_index + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with("[[") && line.ends_with("]]") {
+                section = line[2..line.len()-2].trim().to_string();
+                match section.as_str() {
+                    "module" => {
+                        project.modules.push(Module{name: String::new(), source: String::new(), optimize: false, short_jumps: false});
+                        module_lines.push(line_number);
+                    },
+                    "link" => {
+                        project.links.push(Link{output: String::new(), modules: Vec::new()});
+                        link_lines.push(line_number);
+                    },
+                    _ => return manifest_error(line_number, &format!("unknown table [[{}]]", section))
+                }
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                section = line[1..line.len()-1].trim().to_string();
+                if section != "defines" {
+                    return manifest_error(line_number, &format!("unknown table [{}]", section));
+                }
+                continue;
+            }
+
+            let equals = match line.find('=') {
+                Some(equals) => equals,
+                None => return manifest_error(line_number, "expected key = value")
+            };
+            let key = line[..equals].trim();
+            let value = parse_value(line[equals+1..].trim(), line_number)?;
+            match (section.as_str(), key, value) {
+                ("", "include", Value::List(dirs)) => project.include_dirs = dirs.iter().map(|include| join(dir, include)).collect(),
+                ("", "build", Value::Text(build_dir)) => project.build_dir = join(dir, &build_dir),
+                ("defines", name, Value::Text(value)) => project.defines.push((name.to_string(), value)),
+                ("module", "name", Value::Text(name)) => project.modules.last_mut().unwrap().name = name,
+                ("module", "source", Value::Text(source)) => project.modules.last_mut().unwrap().source = join(dir, &source),
+                ("module", "optimize", Value::Bool(optimize)) => project.modules.last_mut().unwrap().optimize = optimize,
+                ("module", "short_jumps", Value::Bool(short_jumps)) => project.modules.last_mut().unwrap().short_jumps = short_jumps,
+                ("link", "output", Value::Text(output)) => project.links.last_mut().unwrap().output = join(dir, &output),
+                ("link", "modules", Value::List(modules)) => project.links.last_mut().unwrap().modules = modules,
+                _ => return manifest_error(line_number, &format!("unknown key {} or wrong kind of value", key))
+            }
+        }
+
+        for (i, module) in project.modules.iter_mut().enumerate() {
+            if module.source.is_empty() {
+                return manifest_error(module_lines[i], "every module needs a source");
+            }
+            if module.name.is_empty() {
+                module.name = module_name(&module.source);
+            }
+        }
+        // both would be written to the same build/name.bin
+        for (i, module) in project.modules.iter().enumerate() {
+            if project.modules[..i].iter().any(|other| other.name == module.name) {
+                return manifest_error(module_lines[i], &format!("module name {} is already used", module.name));
+            }
+        }
+        for (i, link) in project.links.iter().enumerate() {
+            if link.output.is_empty() {
+                return manifest_error(link_lines[i], "every link needs an output");
+            }
+            for name in &link.modules {
+                if !project.modules.iter().any(|module| module.name == *name) {
+                    return Err(Error::InvalidManifest(format!("link {} names unknown module {}", link.output, name)));
+                }
+            }
+        }
+        return Ok(project);
+    }
+
+    pub fn from_file(path: &str) -> Result<Project, Error> {
+        let text = read_file(path)?;
+        let dir = Path::new(path).parent().map(|dir| dir.to_string_lossy().to_string()).unwrap_or_default();
+        return Project::parse(&text, &dir);
+    }
+
+    // the sources of all modules and the includes they had when they were last built
+    pub fn inputs(&self) -> Vec<String> {
+        let mut inputs: Vec<String> = self.modules.iter().map(|module| module.source.to_string()).collect();
+        for (_, _, includes) in self.read_state().modules {
+            for include in includes {
+                if !inputs.contains(&include) {
+                    inputs.push(include);
+                }
+            }
+        }
+        return inputs;
+    }
+
+    pub fn module_output(&self, module: &Module) -> String {
+        return join(&self.build_dir, &format!("{}.bin", module.name));
+    }
+
+    fn options(&self, module: &Module) -> Options {
+        let mut options = Options::new();
+        options.optimize = module.optimize;
+        options.short_jumps = module.short_jumps;
+        options.include_dirs = self.include_dirs.clone();
+        options.defines = self.defines.clone();
+        return options;
+    }
+
+    // everything the output of a module depends on, None when one of the files can not be read anymore
+    fn fingerprint(&self, module: &Module, includes: &Vec<String>) -> Option<u64> {
+        let mut fingerprint = hash(0xcbf29ce484222325, read_file(&module.source).ok()?.as_bytes());
+        for include in includes {
+            fingerprint = hash(fingerprint, include.as_bytes());
+            fingerprint = hash(fingerprint, read_file(include).ok()?.as_bytes());
+        }
+        let options = format!("{} {} {:?} {:?}", module.optimize, module.short_jumps, self.include_dirs, self.defines);
+        return Some(hash(fingerprint, options.as_bytes()));
+    }
+
+    // everything the output of a link depends on: which modules it takes, in order, and what they assembled to
+    fn link_fingerprint(&self, link: &Link, modules: &Vec<Vec<u8>>) -> u64 {
+        let mut fingerprint = 0xcbf29ce484222325;
+        for (name, bytes) in link.modules.iter().zip(modules) {
+            fingerprint = hash(fingerprint, name.as_bytes());
+            fingerprint = hash(fingerprint, bytes);
+        }
+        return fingerprint;
+    }
+
+    // one line per module with its name, fingerprint and includes, then one per link with its output and fingerprint
+    fn read_state(&self) -> State {
+        let mut state = State{modules: Vec::new(), links: Vec::new()};
+        let text = match read_file(&join(&self.build_dir, STATE_FILE)) {
+            Ok(text) => text,
+            Err(_) => return state
+        };
+        for line in text.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 3 {
+                continue;
+            }
+            let fingerprint = match u64::from_str_radix(fields[2], 16) {
+                Ok(fingerprint) => fingerprint,
+                Err(_) => continue
+            };
+            match fields[0] {
+                "module" => state.modules.push((fields[1].to_string(), fingerprint, fields[3..].iter().map(|include| include.to_string()).collect())),
+                "link" => state.links.push((fields[1].to_string(), fingerprint)),
+                _ => continue
+            }
+        }
+        return state;
+    }
+
+    fn write_state(&self, state: &State) -> Result<(), Error> {
+        let mut text = String::new();
+        for (name, fingerprint, includes) in &state.modules {
+            text.push_str(&format!("module\t{}\t{:016x}", name, fingerprint));
+            for include in includes {
+                text.push_str(&format!("\t{}", include));
+            }
+            text.push('\n');
+        }
+        for (output, fingerprint) in &state.links {
+            text.push_str(&format!("link\t{}\t{:016x}\n", output, fingerprint));
+        }
+        return write(&join(&self.build_dir, STATE_FILE), text.as_bytes());
+    }
+
+    // assembles every module whose source, includes or options changed since the last build, or every module with force
+    // links run when their modules or the bytes those assembled to changed since the link last succeeded, or their output is missing
+    // on failure returns the module or link output that failed, the state of what did not succeed stays as it was
+    pub fn build(&self, force: bool) -> Result<Build, (String, Diagnostics)> {
+        let failed = |name: &str, error: Error| (name.to_string(), Diagnostics{errors: vec![error], warnings: Vec::new()});
+        if let Err(error) = fs::create_dir_all(&self.build_dir) {
+            return Err(failed(&self.build_dir, Error::Io(self.build_dir.to_string(), error.to_string())));
+        }
+
+        let previous = self.read_state();
+        let mut state = State{modules: Vec::new(), links: Vec::new()};
+        let mut build = Build{steps: Vec::new(), warnings: Vec::new()};
+        for module in &self.modules {
+            let output = self.module_output(module);
+            let up_to_date = match previous.modules.iter().find(|(name, _, _)| *name == module.name) {
+                Some((_, fingerprint, includes)) if !force && Path::new(&output).is_file() => {
+                    match self.fingerprint(module, includes) {
+                        Some(current) if current == *fingerprint => {
+                            state.modules.push((module.name.to_string(), current, includes.clone()));
+                            true
+                        },
+                        _ => false
+                    }
+                },
+                _ => false
+            };
+            if up_to_date {
+                build.steps.push((module.name.to_string(), false));
+                continue;
+            }
+
+            let assembled = read_file(&module.source).map_err(|error| failed(&module.name, error))
+                .and_then(|source| assemble_named(&source, &module.source, &self.options(module)).map_err(|diagnostics| (module.name.to_string(), diagnostics)))
+                .and_then(|assembly| write(&output, &assembly.to_bytes()).map(|_| assembly).map_err(|error| failed(&module.name, error)));
+            let assembly = match assembled {
+                Ok(assembly) => assembly,
+                Err(failure) => {
+                    // the last state of this and the remaining modules stays, so their includes are still known
+                    self.write_state(&state.merge(previous)).map_err(|error| failed(&self.build_dir, error))?;
+                    return Err(failure);
+                }
+            };
+            for warning in assembly.warnings {
+                build.warnings.push((module.name.to_string(), warning));
+            }
+            if let Some(fingerprint) = self.fingerprint(module, &assembly.includes) {
+                state.modules.push((module.name.to_string(), fingerprint, assembly.includes));
+            }
+            build.steps.push((module.name.to_string(), true));
+        }
+
+        for link in &self.links {
+            let mut bytes: Vec<Vec<u8>> = Vec::new();
+            for name in &link.modules {
+                let module = self.modules.iter().find(|module| module.name == *name).unwrap();
+                let path = self.module_output(module);
+                bytes.push(fs::read(&path).map_err(|error| failed(&link.output, Error::Io(path.to_string(), error.to_string())))?);
+            }
+            let fingerprint = self.link_fingerprint(link, &bytes);
+            let up_to_date = !force && Path::new(&link.output).is_file() &&
+                previous.links.iter().any(|(output, previous)| *output == link.output && *previous == fingerprint);
+            if up_to_date {
+                state.links.push((link.output.to_string(), fingerprint));
+                build.steps.push((link.output.to_string(), false));
+                continue;
+            }
+
+            let linked = link.modules.iter().zip(&bytes)
+                .map(|(name, bytes)| VMW::from_bytes(bytes).map(|module| (name.to_string(), module)))
+                .collect::<Result<Vec<(String, VMW)>, Error>>()
+                .and_then(|modules| linker::link(&modules))
+                .and_then(|linked| write(&link.output, &linked.to_bytes()));
+            if let Err(error) = linked {
+                // without an entry for this link the next build runs it again, even when no module changed
+                let previous_links = previous.links.into_iter().filter(|(output, _)| *output != link.output).collect();
+                self.write_state(&state.merge(State{modules: previous.modules, links: previous_links})).map_err(|error| failed(&self.build_dir, error))?;
+                return Err(failed(&link.output, error));
+            }
+            state.links.push((link.output.to_string(), fingerprint));
+            build.steps.push((link.output.to_string(), true));
+        }
+        self.write_state(&state).map_err(|error| failed(&self.build_dir, error))?;
+        return Ok(build);
+    }
+}
+
+// what the last build left behind, written to STATE_FILE in the build directory
+struct State {
+    // module name, fingerprint, includes of the last build
+    modules: Vec<(String, u64, Vec<String>)>,
+    // link output, fingerprint of the modules it was linked from
+    links: Vec<(String, u64)>
+}
+
+impl State {
+    // adds the entries of previous that this state does not have yet
+    fn merge(mut self, previous: State) -> State {
+        for entry in previous.modules {
+            if !self.modules.iter().any(|(name, _, _)| *name == entry.0) {
+                self.modules.push(entry);
+            }
+        }
+        for entry in previous.links {
+            if !self.links.iter().any(|(output, _)| *output == entry.0) {
+                self.links.push(entry);
+            }
+        }
+        return self;
+    }
+}
+
+fn write(path: &str, contents: &[u8]) -> Result<(), Error> {
+    match File::create(path).and_then(|mut f| f.write_all(contents)) {
+        Ok(_) => return Ok(()),
+        Err(error) => return Err(Error::Io(path.to_string(), error.to_string()))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::Path;
+use error::Error;
+use read_file;
+
+// where include finds the files it names, the preprocessor asks for every candidate path in order
+pub trait Resolver {
+    fn exists(&self, path: &str) -> bool;
+    fn read(&self, path: &str) -> Result<String, Error>;
+    // one spelling for every path naming the same file, so include cycles are found
+    fn canonical(&self, path: &str) -> String;
+}
+
+// the files on disk
+pub struct FileResolver;
+
+impl Resolver for FileResolver {
+    fn exists(&self, path: &str) -> bool {
+        return Path::new(path).is_file();
+    }
+
+    fn read(&self, path: &str) -> Result<String, Error> {
+        return read_file(path);
+    }
+
+    // a file that cannot be resolved keeps its path, reading it reports why
+    fn canonical(&self, path: &str) -> String {
+        return fs::canonicalize(path).map(|path| path.to_string_lossy().to_string()).unwrap_or(path.to_string());
+    }
+}
+
+// files held in memory, for hosts without a filesystem like the browser
+// paths are compared after removing . and resolving .., so lib/../a.asm finds a.asm
+#[derive(Default)]
+pub struct MemoryResolver {
+    // path, contents
+    pub files: Vec<(String, String)>
+}
+
+impl MemoryResolver {
+    pub fn new() -> MemoryResolver {
+        return MemoryResolver{files: Vec::new()};
+    }
+
+    pub fn add(&mut self, path: &str, contents: &str) {
+        let path = normalize(path);
+        self.files.retain(|(file, _)| normalize(file) != path);
+        self.files.push((path, contents.to_string()));
+    }
+
+    fn find(&self, path: &str) -> Option<&str> {
+        let path = normalize(path);
+        return self.files.iter().find(|(file, _)| normalize(file) == path).map(|(_, contents)| contents.as_str());
+    }
+}
+
+impl Resolver for MemoryResolver {
+    fn exists(&self, path: &str) -> bool {
+        return self.find(path).is_some();
+    }
+
+    fn read(&self, path: &str) -> Result<String, Error> {
+        return self.find(path).map(|contents| contents.to_string()).ok_or(Error::Io(path.to_string(), "no such file".to_string()));
+    }
+
+    fn canonical(&self, path: &str) -> String {
+        return normalize(path);
+    }
+}
+
+fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split(['/', '\\']) {
+        match part {
+            "" | "." => {},
+            ".." if !parts.is_empty() && *parts.last().unwrap() != ".." => { parts.pop(); },
+            _ => parts.push(part)
+        }
+    }
+    let normalized = parts.join("/");
+    return if path.starts_with('/') { format!("/{}", normalized) } else { normalized };
+}
\ No newline at end of file
@@ -0,0 +1,149 @@
+use ast::{Tree, Procedure, Operation, Address, Signature};
+use error::{Error, Warning};
+use vm::{Opcode, Type};
+use std::collections::HashMap;
+use cfg::jump_target;
+
+// stack heights in bytes, relative to the height at the start of the procedure
+pub struct StackReport {
+    pub procedure: String,
+    pub heights: Vec<Option<i64>>, // before every operation, None when no path reaches it
+    pub max_depth: i64
+}
+
+fn size(types: &[Type]) -> i64 {
+    return types.iter().map(|t| t.size() as i64).sum();
+}
+
+// change of the stack height caused by an operation, None when it does not fit an i64
+// calls to procedures of this module with a signature push the returns, the callee copies the parameters
+// and the caller pops them after the call, any other call is assumed to leave the stack as it was
+fn effect(operation: &Operation, signatures: &HashMap<&str, &Signature>) -> Option<i64> {
+    let instruction = operation.opcode.instruction();
+    match (operation.opcode, &operation.operands[..]) {
+        (Opcode::Spi, [Address::IntLiteral(value)]) if *value <= i64::MAX as u64 => return Some(*value as i64),
+        (Opcode::Spd, [Address::IntLiteral(value)]) if *value <= i64::MAX as u64 => return Some(-(*value as i64)),
+        (Opcode::Spi, [Address::IntLiteral(_)]) | (Opcode::Spd, [Address::IntLiteral(_)]) => return None,
+        (Opcode::Call, [Address::ProcRef(called)]) => match signatures.get(called.as_str()) {
+            Some(signature) => return Some(size(&signature.returns)),
+            None => return Some(0)
+        },
+        _ => return Some(instruction.pushes as i64 - instruction.pops as i64)
+    }
+}
+
+// returns: operation indices control continues at after the operation, with the stack height there
+// jumps to other procedures leave this one, a jump to its own start loops back to the first operation
+// pushing the address of a label makes that label a return point, reached with the height before the push
+fn successors(name: &str, procedure: &Procedure, labels: &HashMap<&str, usize>, op_index: usize, height: i64, after: i64) -> Vec<(usize, i64)> {
+    let operation = &procedure.operations[op_index];
+    let mut next: Vec<(usize, i64)> = Vec::new();
+    match operation.opcode {
+        Opcode::Jmp | Opcode::JmpRel8 | Opcode::JmpRel16 => {
+            if let Some(index) = jump_target(name, procedure, labels, op_index) {
+                next.push((index, after));
+            }
+        },
+        Opcode::JmpTrue | Opcode::JmpTrueRel8 | Opcode::JmpTrueRel16 | Opcode::JmpFalse | Opcode::JmpFalseRel8 | Opcode::JmpFalseRel16 => {
+            if let Some(index) = jump_target(name, procedure, labels, op_index) {
+                next.push((index, after));
+            }
+            next.push((op_index + 1, after));
+        },
+        Opcode::Jmps | Opcode::Ret | Opcode::Halt => {},
+        Opcode::PushU64 => {
+            if let Address::Label(label) = &operation.operands[0] {
+                if let Some(index) = labels.get(label.as_str()) {
+                    next.push((*index, height));
+                }
+            }
+            next.push((op_index + 1, after));
+        },
+        _ => {
+            next.push((op_index + 1, after));
+        }
+    }
+    return next;
+}
+
+// follows every control flow path through every procedure and records the stack height before each operation
+// a label or loop reached with different heights is reported as a warning, a height below the start of the procedure too,
+// a height out of the range of an i64 is an error and ends the path
+pub fn analyse(source: &Tree) -> (Vec<StackReport>, Vec<Warning>, Vec<Error>) {
+    let mut reports: Vec<StackReport> = Vec::new();
+    let mut warnings: Vec<Warning> = Vec::new();
+    let mut errors: Vec<Error> = Vec::new();
+    let mut signatures: HashMap<&str, &Signature> = HashMap::new();
+    for (name, procedure) in &source.procedures {
+        if let Some(signature) = &procedure.signature {
+            signatures.insert(name, signature);
+        }
+    }
+
+    for (name, procedure) in &source.procedures {
+        let mut labels: HashMap<&str, usize> = HashMap::new();
+        for (label, index) in &procedure.labels {
+            labels.insert(label, *index);
+        }
+
+        // one more entry for falling off the end of the procedure
+        let mut heights: Vec<Option<i64>> = vec![None; procedure.operations.len() + 1];
+        let mut reported: Vec<bool> = vec![false; procedure.operations.len() + 1];
+        let mut pending: Vec<usize> = vec![0];
+        let mut below_start = false;
+        heights[0] = Some(0);
+        while let Some(op_index) = pending.pop() {
+            if op_index == procedure.operations.len() {
+                continue;
+            }
+            let height = heights[op_index].unwrap();
+            // a procedure may pop what its caller pushed, the way older code passes arguments
+            if height < 0 && !below_start {
+                below_start = true;
+                warnings.push(Warning::StackUnderflow(name.to_string(), op_index, height));
+            }
+            let after = match effect(&procedure.operations[op_index], &signatures).and_then(|effect| height.checked_add(effect)) {
+                Some(after) => after,
+                None => {
+                    errors.push(Error::StackOutOfRange(name.to_string(), op_index));
+                    continue;
+                }
+            };
+            for (next, next_height) in successors(name, procedure, &labels, op_index, height, after) {
+                match heights[next] {
+                    None => {
+                        heights[next] = Some(next_height);
+                        pending.push(next);
+                    },
+                    Some(known) => {
+                        if known != next_height && !reported[next] {
+                            reported[next] = true;
+                            warnings.push(Warning::StackHeight(name.to_string(), next, known, next_height));
+                        }
+                    }
+                }
+            }
+        }
+
+        let max_depth = heights.iter().filter_map(|height| *height).max().unwrap_or(0);
+        heights.pop();
+        reports.push(StackReport{procedure: name.to_string(), heights: heights, max_depth: max_depth});
+    }
+    return (reports, warnings, errors);
+}
+
+// the stack height before every operation, - where no path reaches it
+pub fn to_text(source: &Tree, reports: &[StackReport]) -> String {
+    let mut text = String::new();
+    for (report, (_, procedure)) in reports.iter().zip(source.procedures.iter()) {
+        text.push_str(&format!("proc {}: max stack depth {} bytes\n", report.procedure, report.max_depth));
+        for (op_index, height) in report.heights.iter().enumerate() {
+            let mnemonic = procedure.operations[op_index].opcode.instruction().mnemonic;
+            match height {
+                Some(height) => text.push_str(&format!("{:6} {:6} {}\n", op_index, height, mnemonic)),
+                None => text.push_str(&format!("{:6} {:>6} {}\n", op_index, "-", mnemonic))
+            }
+        }
+    }
+    return text;
+}
\ No newline at end of file
@@ -1,15 +1,198 @@
-pub enum OpcodeValues {
-    Jmp = 0,
-    Jmps = 1,
-    JmpTrue = 2,
-    CmpU8 = 3,
-    Spi = 4,
-    Spd = 5,
-    PushU8 = 6,
-    PushU64 = 7,
-    PopU8 = 8,
-    SetU8 = 9,
-    CplU8 = 10,
-    CpgU8 = 11,
-    Halt = 12
-}
\ No newline at end of file
+use error::AddressKind;
+
+const ANY_ADDRESS: &[AddressKind] = &[AddressKind::IntLiteral, AddressKind::Label, AddressKind::ProcRef, AddressKind::ExtProcRef];
+const INT_LITERAL: &[AddressKind] = &[AddressKind::IntLiteral];
+const RELATIVE: &[AddressKind] = &[AddressKind::IntLiteral, AddressKind::Label];
+
+// an operand is encoded big endian in `width` bytes directly after the u16 opcode
+pub struct Operand {
+    pub width: usize,
+    pub accepts: &'static [AddressKind]
+}
+
+// immediate values
+pub const U8: Operand = Operand{width: 1, accepts: INT_LITERAL};
+pub const U16: Operand = Operand{width: 2, accepts: INT_LITERAL};
+pub const U32: Operand = Operand{width: 4, accepts: INT_LITERAL};
+pub const U64: Operand = Operand{width: 8, accepts: INT_LITERAL};
+// u64 that may also be resolved from a label or (external) procedure reference
+pub const ADDRESS: Operand = Operand{width: 8, accepts: ANY_ADDRESS};
+// two's complement displacement from the end of the instruction, or a label within the same procedure
+pub const REL8: Operand = Operand{width: 1, accepts: RELATIVE};
+pub const REL16: Operand = Operand{width: 2, accepts: RELATIVE};
+
+// types of values on the stack, as used in procedure signatures
+#[derive(Clone, Copy, PartialEq)]
+pub enum Type {
+    U8,
+    U16,
+    U32,
+    U64
+}
+
+impl Type {
+    pub fn size(&self) -> u64 {
+        match self {
+            Type::U8 => 1,
+            Type::U16 => 2,
+            Type::U32 => 4,
+            Type::U64 => 8
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Type::U8 => "u8",
+            Type::U16 => "u16",
+            Type::U32 => "u32",
+            Type::U64 => "u64"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Type> {
+        match name {
+            "u8" => Some(Type::U8),
+            "u16" => Some(Type::U16),
+            "u32" => Some(Type::U32),
+            "u64" => Some(Type::U64),
+            _ => None
+        }
+    }
+}
+
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub mnemonic: &'static str,
+    pub operands: &'static [Operand],
+    // bytes taken from and put on the stack
+    pub pops: u64,
+    pub pushes: u64
+}
+
+macro_rules! instruction_set {
+    ($($name:ident = $value:expr, $mnemonic:expr, [$($operand:expr),*], $pops:expr, $pushes:expr;)*) => {
+        #[derive(Clone, Copy, PartialEq)]
+        pub enum Opcode {
+            $($name = $value),*
+        }
+
+        pub const INSTRUCTIONS: &[Instruction] = &[
+            $(Instruction{opcode: Opcode::$name, mnemonic: $mnemonic, operands: &[$($operand),*], pops: $pops, pushes: $pushes}),*
+        ];
+    }
+}
+
+// the instruction set of the vm, adding an instruction only requires an entry here
+// every entry is: name = opcode, mnemonic, [operands], bytes popped, bytes pushed
+// spi and spd move the stack pointer by their operand and call by whatever the callee leaves behind,
+// so their effect is not part of the table
+instruction_set! {
+    Jmp = 0, "jmp", [ADDRESS], 0, 0;
+    Jmps = 1, "jmps", [], 8, 0;
+    // conditional jumps leave the result of the comparison on the stack
+    JmpTrue = 2, "jmp_true", [ADDRESS], 0, 0;
+    CmpU8 = 3, "cmp_u8", [], 2, 1;
+    // spi grows the stack by its operand, spd shrinks it
+    Spi = 4, "spi", [U64], 0, 0;
+    Spd = 5, "spd", [U64], 0, 0;
+    PushU8 = 6, "push_u8", [U8], 0, 1;
+    PushU64 = 7, "push_u64", [ADDRESS], 0, 8;
+    PopU8 = 8, "pop_u8", [], 1, 0;
+    SetU8 = 9, "set_u8", [ADDRESS], 1, 0;
+    // copies relative to the stack pointer, so only an offset makes sense
+    CplU8 = 10, "cpl_u8", [U64], 0, 1;
+    CpgU8 = 11, "cpg_u8", [ADDRESS], 0, 1;
+    Halt = 12, "halt", [], 0, 0;
+    // arithmetic pops the right hand side, then the left hand side and pushes the result
+    AddU8 = 13, "add_u8", [], 2, 1;
+    SubU8 = 14, "sub_u8", [], 2, 1;
+    MulU8 = 15, "mul_u8", [], 2, 1;
+    DivU8 = 16, "div_u8", [], 2, 1;
+    ModU8 = 17, "mod_u8", [], 2, 1;
+    AddU64 = 18, "add_u64", [], 16, 8;
+    SubU64 = 19, "sub_u64", [], 16, 8;
+    MulU64 = 20, "mul_u64", [], 16, 8;
+    DivU64 = 21, "div_u64", [], 16, 8;
+    ModU64 = 22, "mod_u64", [], 16, 8;
+    // bitwise operations work like arithmetic, not pops and pushes a single value
+    // shifts pop a u8 shift amount, then the value to shift
+    AndU8 = 23, "and_u8", [], 2, 1;
+    OrU8 = 24, "or_u8", [], 2, 1;
+    XorU8 = 25, "xor_u8", [], 2, 1;
+    NotU8 = 26, "not_u8", [], 1, 1;
+    ShlU8 = 27, "shl_u8", [], 2, 1;
+    ShrU8 = 28, "shr_u8", [], 2, 1;
+    AndU64 = 29, "and_u64", [], 16, 8;
+    OrU64 = 30, "or_u64", [], 16, 8;
+    XorU64 = 31, "xor_u64", [], 16, 8;
+    NotU64 = 32, "not_u64", [], 8, 8;
+    ShlU64 = 33, "shl_u64", [], 9, 8;
+    ShrU64 = 34, "shr_u64", [], 9, 8;
+    // comparisons pop the right hand side, then the left hand side like cmp_u8 and push the result
+    // the i variants compare as two's complement signed values
+    CmpU64 = 35, "cmp_u64", [], 16, 1;
+    CmpNeU8 = 36, "cmp_ne_u8", [], 2, 1;
+    CmpNeU64 = 37, "cmp_ne_u64", [], 16, 1;
+    CmpLtU8 = 38, "cmp_lt_u8", [], 2, 1;
+    CmpLtI8 = 39, "cmp_lt_i8", [], 2, 1;
+    CmpLeU8 = 40, "cmp_le_u8", [], 2, 1;
+    CmpLeI8 = 41, "cmp_le_i8", [], 2, 1;
+    CmpGtU8 = 42, "cmp_gt_u8", [], 2, 1;
+    CmpGtI8 = 43, "cmp_gt_i8", [], 2, 1;
+    CmpGeU8 = 44, "cmp_ge_u8", [], 2, 1;
+    CmpGeI8 = 45, "cmp_ge_i8", [], 2, 1;
+    CmpLtU64 = 46, "cmp_lt_u64", [], 16, 1;
+    CmpLtI64 = 47, "cmp_lt_i64", [], 16, 1;
+    CmpLeU64 = 48, "cmp_le_u64", [], 16, 1;
+    CmpLeI64 = 49, "cmp_le_i64", [], 16, 1;
+    CmpGtU64 = 50, "cmp_gt_u64", [], 16, 1;
+    CmpGtI64 = 51, "cmp_gt_i64", [], 16, 1;
+    CmpGeU64 = 52, "cmp_ge_u64", [], 16, 1;
+    CmpGeI64 = 53, "cmp_ge_i64", [], 16, 1;
+    JmpFalse = 54, "jmp_false", [ADDRESS], 0, 0;
+    PushU16 = 55, "push_u16", [U16], 0, 2;
+    PushU32 = 56, "push_u32", [U32], 0, 4;
+    PopU16 = 57, "pop_u16", [], 2, 0;
+    PopU32 = 58, "pop_u32", [], 4, 0;
+    PopU64 = 59, "pop_u64", [], 8, 0;
+    SetU16 = 60, "set_u16", [ADDRESS], 2, 0;
+    SetU32 = 61, "set_u32", [ADDRESS], 4, 0;
+    SetU64 = 62, "set_u64", [ADDRESS], 8, 0;
+    CplU16 = 63, "cpl_u16", [U64], 0, 2;
+    CplU32 = 64, "cpl_u32", [U64], 0, 4;
+    CplU64 = 65, "cpl_u64", [U64], 0, 8;
+    CpgU16 = 66, "cpg_u16", [ADDRESS], 0, 2;
+    CpgU32 = 67, "cpg_u32", [ADDRESS], 0, 4;
+    CpgU64 = 68, "cpg_u64", [ADDRESS], 0, 8;
+    // call pushes the u64 address of the next operation and jumps, ret pops it and jumps back
+    Call = 69, "call", [ADDRESS], 0, 0;
+    Ret = 70, "ret", [], 8, 0;
+    // short jumps, the generator picks these for jmp, jmp_true and jmp_false when the label is close enough
+    JmpRel8 = 71, "jmp_rel8", [REL8], 0, 0;
+    JmpRel16 = 72, "jmp_rel16", [REL16], 0, 0;
+    JmpTrueRel8 = 73, "jmp_true_rel8", [REL8], 0, 0;
+    JmpTrueRel16 = 74, "jmp_true_rel16", [REL16], 0, 0;
+    JmpFalseRel8 = 75, "jmp_false_rel8", [REL8], 0, 0;
+    JmpFalseRel16 = 76, "jmp_false_rel16", [REL16], 0, 0;
+}
+
+impl Opcode {
+    pub fn instruction(&self) -> &'static Instruction {
+        return INSTRUCTIONS.iter().find(|instruction| instruction.opcode == *self).expect("opcode missing from instruction set");
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Opcode> {
+        return INSTRUCTIONS.iter().find(|instruction| instruction.mnemonic == mnemonic).map(|instruction| instruction.opcode);
+    }
+
+    pub fn from_value(value: u16) -> Option<Opcode> {
+        return INSTRUCTIONS.iter().find(|instruction| instruction.opcode as u16 == value).map(|instruction| instruction.opcode);
+    }
+}
+
+impl Instruction {
+    // encoded size in bytes, including the opcode
+    pub fn size(&self) -> u64 {
+        return 2 + self.operands.iter().map(|operand| operand.width as u64).sum::<u64>();
+    }
+}
@@ -0,0 +1,61 @@
+use std::fs;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+// polls files for changes, no notification service of the os required
+pub struct Watcher {
+    // path, modification time and size or None when the file can not be read
+    files: Vec<(String, Option<(SystemTime, u64)>)>
+}
+
+fn stamp(path: &str) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    return Some((metadata.modified().ok()?, metadata.len()));
+}
+
+impl Watcher {
+    pub fn new(paths: &[String]) -> Watcher {
+        return Watcher{files: paths.iter().map(|path| (path.to_string(), stamp(path))).collect()};
+    }
+
+    // whether a file was changed, created or removed since the last call
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        for (path, last) in self.files.iter_mut() {
+            let current = stamp(path);
+            if current != *last {
+                *last = current;
+                changed = true;
+            }
+        }
+        return changed;
+    }
+
+    // returns after the first change
+    pub fn wait(&mut self, interval: Duration) {
+        while !self.changed() {
+            thread::sleep(interval);
+        }
+    }
+}
+
+// how often watch looks at the files
+pub const INTERVAL: Duration = Duration::from_millis(250);
+
+// runs, then runs again every time one of the files run returns changes, forever
+pub fn repeat<F: FnMut() -> Vec<String>>(mut run: F, interval: Duration) -> ! {
+    loop {
+        let files = run();
+        Watcher::new(&files).wait(interval);
+    }
+}
+
+// like repeat, for a run that returns whether it succeeded and the files it read
+// done gets both after every run, to say what happened
+pub fn watch<F: FnMut() -> (bool, Vec<String>), D: FnMut(bool, &Vec<String>)>(mut run: F, mut done: D) -> ! {
+    repeat(|| {
+        let (succeeded, files) = run();
+        done(succeeded, &files);
+        return files;
+    }, INTERVAL);
+}
\ No newline at end of file
@@ -0,0 +1,104 @@
+use json;
+use json::Json;
+use resolver::MemoryResolver;
+use {Options, assemble_with_resolver};
+
+// assembles without touching the filesystem, for the browser and other embedders
+// request: {"source": text, "path": "main.asm", "files": {path: text}, "defines": {name: value},
+//           "optimize": false, "short_jumps": false, "lower_calls": false, "legacy": false}
+// only source is required, includes are looked up in files relative to path
+// response: {"ok": bool, "bytes": vmw module as base64 or null, "origin": org or null,
+//            "diagnostics": [{"severity": "error" or "warning", "message": text}], "map": symbols or null}
+pub fn assemble_request(request: &str) -> String {
+    let request = match Json::parse(request) {
+        Ok(request) => request,
+        Err(error) => return failure(vec![("error", error.to_string())])
+    };
+    let source = match request.get("source").and_then(Json::as_str) {
+        Some(source) => source,
+        None => return failure(vec![("error", "request has no source".to_string())])
+    };
+    let path = request.get("path").and_then(Json::as_str).unwrap_or("main.asm");
+
+    let mut resolver = MemoryResolver::new();
+    if let Some(Json::Object(files)) = request.get("files") {
+        for (file, contents) in files {
+            resolver.add(file, contents.as_str().unwrap_or(""));
+        }
+    }
+    let mut options = Options::new();
+    let flag = |name: &str| request.get(name).and_then(Json::as_bool).unwrap_or(false);
+    options.optimize = flag("optimize");
+    options.short_jumps = flag("short_jumps");
+    options.lower_calls = flag("lower_calls");
+    options.legacy = flag("legacy");
+    if let Some(Json::Object(defines)) = request.get("defines") {
+        for (name, value) in defines {
+            let value = match value {
+                Json::String(value) => value.to_string(),
+                value => value.to_string()
+            };
+            options.defines.push((name.to_string(), value));
+        }
+    }
+
+    match assemble_with_resolver(source, path, &options, &resolver) {
+        Ok(assembly) => {
+            return Json::object(vec![
+                ("ok", Json::Bool(true)),
+                ("bytes", Json::String(json::to_base64(&assembly.to_bytes()))),
+                ("origin", assembly.origin.map(Json::from_u64).unwrap_or(Json::Null)),
+                ("diagnostics", diagnostics(assembly.warnings.iter().map(|warning| ("warning", warning.to_string())).collect())),
+                ("map", assembly.map.to_json())
+            ]).to_string();
+        },
+        Err(diagnostics) => {
+            let mut messages: Vec<(&str, String)> = diagnostics.errors.iter().map(|error| ("error", error.to_string())).collect();
+            messages.extend(diagnostics.warnings.iter().map(|warning| ("warning", warning.to_string())));
+            return failure(messages);
+        }
+    }
+}
+
+// severity, message
+fn diagnostics(messages: Vec<(&str, String)>) -> Json {
+    return Json::Array(messages.into_iter().map(|(severity, message)| Json::object(vec![
+        ("severity", Json::from_text(severity)), ("message", Json::String(message))
+    ])).collect());
+}
+
+fn failure(messages: Vec<(&str, String)>) -> String {
+    return Json::object(vec![
+        ("ok", Json::Bool(false)), ("bytes", Json::Null), ("origin", Json::Null), ("diagnostics", diagnostics(messages)), ("map", Json::Null)
+    ]).to_string();
+}
+
+// the c abi of the wasm build, strings cross as utf-8 in memory of the module:
+// the host gets a buffer with vmw_alloc, writes the request into it and calls vmw_assemble,
+// which returns a buffer starting with the little endian u32 length of the response that follows,
+// every buffer is given back with vmw_free and the length it was allocated with
+#[cfg(target_arch = "wasm32")]
+pub mod exports {
+    use std::slice;
+    use super::assemble_request;
+
+    #[no_mangle]
+    pub extern "C" fn vmw_alloc(len: usize) -> *mut u8 {
+        return Box::into_raw(vec![0u8; len].into_boxed_slice()) as *mut u8;
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn vmw_free(ptr: *mut u8, len: usize) {
+        drop(Box::from_raw(slice::from_raw_parts_mut(ptr, len)));
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn vmw_assemble(ptr: *const u8, len: usize) -> *mut u8 {
+        let request = String::from_utf8_lossy(slice::from_raw_parts(ptr, len)).to_string();
+        let response = assemble_request(&request);
+        let mut buffer: Vec<u8> = Vec::new();
+        buffer.extend_from_slice(&(response.len() as u32).to_le_bytes());
+        buffer.extend_from_slice(response.as_bytes());
+        return Box::into_raw(buffer.into_boxed_slice()) as *mut u8;
+    }
+}
\ No newline at end of file
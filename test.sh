@@ -1 +1,396 @@
-cargo run test/printcstr.asm test/printcstr.bin
\ No newline at end of file
+cargo build
+
+# every test/*.asm must assemble to its committed test/*.bin
+for f in test/*.asm; do
+    target/debug/vmw_assembler assemble $f -o test/output.bin
+    if ! cmp -s test/output.bin ${f%.asm}.bin; then
+        echo "$f: output differs from ${f%.asm}.bin"
+        exit 1
+    fi
+done
+
+# the symbol map lists every procedure and label with its offset, size and visibility, -g writes it next to the output
+target/debug/vmw_assembler assemble --map test/output.map test/procedures.asm -o test/output.bin
+if ! cmp -s test/output.map test/procedures.map; then
+    echo "test/procedures.asm: map differs from test/procedures.map"
+    exit 1
+fi
+rm test/output.map
+target/debug/vmw_assembler assemble -g test/procedures.asm -o test/output.bin
+if ! cmp -s test/output.bin.map test/procedures.map; then
+    echo "test/procedures.asm: map written by -g differs from test/procedures.map"
+    exit 1
+fi
+rm test/output.bin.map
+
+# call and ret lowered to push_u64, jmp and jmps for vms without them
+target/debug/vmw_assembler assemble --lower-calls test/call.asm -o test/output.bin
+if ! cmp -s test/output.bin test/call.lowered.bin; then
+    echo "test/call.asm: lowered output differs from test/call.lowered.bin"
+    exit 1
+fi
+
+# the return address pushed for a lowered call is not taken for an argument
+if target/debug/vmw_assembler check --lower-calls test/signatures.asm 2>&1 | grep -q "declared as"; then
+    echo "check --lower-calls test/signatures.asm: expected no argument warning"
+    exit 1
+fi
+if ! target/debug/vmw_assembler check --lower-calls test/warnings/arguments.asm 2>&1 | grep -q "declared as (u8)->() but the caller pushes (u64)"; then
+    echo "check --lower-calls test/warnings/arguments.asm: expected an argument warning"
+    exit 1
+fi
+
+# peephole optimizations
+target/debug/vmw_assembler assemble -O test/optimize.asm -o test/output.bin 2> /dev/null
+if ! cmp -s test/output.bin test/optimize.optimized.bin; then
+    echo "test/optimize.asm: optimized output differs from test/optimize.optimized.bin"
+    exit 1
+fi
+target/debug/vmw_assembler assemble -O test/optimize/stack_pointer_overflow.asm -o /dev/null 2> /dev/null
+if [ $? -ne 0 ]; then
+    echo "test/optimize/stack_pointer_overflow.asm: expected spi and spd that overflow to stay unmerged"
+    exit 1
+fi
+# a literal displacement does not follow the operations the rules remove, so its procedure is left as it is
+target/debug/vmw_assembler assemble -O test/optimize/literal_jump.asm -o test/output.bin 2> /dev/null
+if ! cmp -s test/output.bin test/optimize/literal_jump.optimized.bin; then
+    echo "test/optimize/literal_jump.asm: optimized output differs from test/optimize/literal_jump.optimized.bin"
+    exit 1
+fi
+
+# jumps relaxed to the smallest encoding
+target/debug/vmw_assembler assemble --short-jumps test/short_jumps.asm -o test/output.bin
+if ! cmp -s test/output.bin test/short_jumps.short.bin; then
+    echo "test/short_jumps.asm: output differs from test/short_jumps.short.bin"
+    exit 1
+fi
+
+# - reads stdin and writes stdout, fmt output assembles to the same bytes
+target/debug/vmw_assembler assemble - < test/procedures.asm > test/output.bin
+if ! cmp -s test/output.bin test/procedures.bin; then
+    echo "test/procedures.asm: output through stdin and stdout differs from test/procedures.bin"
+    exit 1
+fi
+target/debug/vmw_assembler fmt test/signatures.asm | target/debug/vmw_assembler assemble - > test/output.bin
+if ! cmp -s test/output.bin test/signatures.bin; then
+    echo "test/signatures.asm: formatted source assembles differently"
+    exit 1
+fi
+rm test/output.bin
+
+# the same source must always assemble to the same bytes, written aside so the golden is never overwritten
+first=$(mktemp)
+again=$(mktemp)
+target/debug/vmw_assembler assemble test/procedures.asm -o $first
+for i in 2 3 4 5 6 7 8 9 10; do
+    target/debug/vmw_assembler assemble test/procedures.asm -o $again
+    if ! cmp -s $first $again; then
+        rm $first $again
+        echo "test/procedures.asm: output differs between runs"
+        exit 1
+    fi
+done
+if ! cmp -s $first test/procedures.bin; then
+    rm $first $again
+    echo "test/procedures.asm: output differs from test/procedures.bin"
+    exit 1
+fi
+rm $first $again
+
+# sources the assembler must reject with an error instead of a panic
+for f in test/errors/*.asm; do
+    target/debug/vmw_assembler assemble $f -o /dev/null 2> /dev/null
+    if [ $? -ne 1 ]; then
+        echo "$f: expected an error"
+        exit 1
+    fi
+done
+# an include of the file itself under another path is a cycle
+if ! target/debug/vmw_assembler assemble test/include/cycle/a.asm -o /dev/null 2>&1 | grep -q "includes itself"; then
+    echo "test/include/cycle/a.asm: expected an include cycle"
+    exit 1
+fi
+# sources that assemble, but with a warning
+for f in test/warnings/*.asm; do
+    if ! target/debug/vmw_assembler check $f 2>&1 | grep -q "^warning: "; then
+        echo "$f: expected a warning"
+        exit 1
+    fi
+done
+
+# control flow graphs as graphviz dot, relative jumps with a literal displacement land within the procedure
+target/debug/vmw_assembler check --cfg test/cfg/relative.asm > test/output.txt 2> /dev/null
+if ! cmp -s test/output.txt test/cfg/relative.dot; then
+    echo "check --cfg test/cfg/relative.asm: output differs from test/cfg/relative.dot"
+    exit 1
+fi
+target/debug/vmw_assembler check --stack test/cfg/relative.asm > test/output.txt 2> /dev/null
+if ! cmp -s test/output.txt test/cfg/relative.txt; then
+    echo "check --stack test/cfg/relative.asm: output differs from test/cfg/relative.txt"
+    exit 1
+fi
+rm test/output.txt
+
+# stack heights before every operation, calls push what the signature returns and the caller pops the arguments
+target/debug/vmw_assembler check --stack test/stack/loop.asm > test/output.txt
+if ! cmp -s test/output.txt test/stack/loop.txt; then
+    echo "check --stack test/stack/loop.asm: output differs from test/stack/loop.txt"
+    exit 1
+fi
+rm test/output.txt
+
+# programs run on the reference vm must print what is in their .out
+target/debug/vmw_assembler run test/run/hello.asm > test/output.txt
+target/debug/vmw_assembler run test/link/main.asm test/link/console.asm 2> /dev/null >> test/output.txt
+target/debug/vmw_assembler run -D letter=0x41 test/include/main.asm 2> /dev/null >> test/output.txt
+if ! cat test/run/hello.out test/link/main.out test/include/main.out | cmp -s test/output.txt; then
+    echo "run: output differs from the .out files"
+    exit 1
+fi
+rm test/output.txt
+
+# linking resolves the calls between modules, so the result runs on its own
+target/debug/vmw_assembler link test/link/main.asm test/link/console.asm -o test/output.bin 2> /dev/null
+if ! target/debug/vmw_assembler run test/output.bin | cmp -s test/link/main.out; then
+    echo "link: linked module does not run"
+    exit 1
+fi
+rm test/output.bin
+
+# unknown options are usage errors
+# the command line from before there were commands still assembles
+target/debug/vmw_assembler test/procedures.asm test/output.bin
+if ! cmp -s test/output.bin test/procedures.bin; then
+    echo "vmw_assembler infile outfile: output differs from test/procedures.bin"
+    exit 1
+fi
+rm test/output.bin
+target/debug/vmw_assembler assemble --unknown test/procedures.asm 2> /dev/null
+if [ $? -ne 2 ]; then
+    echo "assemble --unknown: expected exit code 2"
+    exit 1
+fi
+
+# a project builds every module once, then only what changed
+rm -rf test/project/build
+target/debug/vmw_assembler build test/project/vmw.toml > /dev/null 2>&1
+if ! target/debug/vmw_assembler run test/project/build/hello.bin | cmp -s test/link/main.out; then
+    echo "build: linked program does not run"
+    exit 1
+fi
+if target/debug/vmw_assembler build test/project/vmw.toml 2>&1 | grep -qv "up to date"; then
+    echo "build: second build was not up to date"
+    exit 1
+fi
+rm -rf test/project/build
+# a link that failed runs again on the next build, even with the output of an earlier link left in place
+project=$(mktemp -d)
+cp test/link/main.asm test/link/console.asm $project
+printf '[[module]]\nsource = "main.asm"\n\n[[module]]\nsource = "console.asm"\n\n[[link]]\noutput = "build/hello.bin"\nmodules = ["main", "console"]' > $project/vmw.toml
+target/debug/vmw_assembler build $project/vmw.toml > /dev/null 2>&1
+sed 's/printc/printd/' test/link/console.asm > $project/console.asm
+target/debug/vmw_assembler build $project/vmw.toml > /dev/null 2>&1
+if target/debug/vmw_assembler build $project/vmw.toml > /dev/null 2>&1; then
+    echo "build: a link that failed was up to date on the next build"
+    exit 1
+fi
+cp test/link/console.asm $project/console.asm
+if ! target/debug/vmw_assembler build $project/vmw.toml 2>&1 | grep -q "linked .*hello.bin"; then
+    echo "build: the fixed link was not rebuilt"
+    exit 1
+fi
+if ! target/debug/vmw_assembler run $project/build/hello.bin | cmp -s test/link/main.out; then
+    echo "build: the fixed link does not run"
+    exit 1
+fi
+rm -rf $project
+# a module name used twice is reported on the table that repeats it
+if ! target/debug/vmw_assembler build test/project/duplicate.toml 2>&1 | grep -q "manifest, line 5: module name main is already used"; then
+    echo "build test/project/duplicate.toml: expected an error for the second module named main"
+    exit 1
+fi
+
+# --watch assembles again when the input changes
+cp test/procedures.asm test/watch.asm
+target/debug/vmw_assembler assemble --watch test/watch.asm -o test/output.bin 2> /dev/null &
+watch_pid=$!
+sleep 1
+if ! cmp -s test/output.bin test/procedures.bin; then
+    kill $watch_pid
+    echo "assemble --watch: first output differs from test/procedures.bin"
+    exit 1
+fi
+cp test/signatures.asm test/watch.asm
+sleep 1
+kill $watch_pid
+if ! cmp -s test/output.bin test/signatures.bin; then
+    echo "assemble --watch: changed input was not assembled again"
+    exit 1
+fi
+rm test/watch.asm test/output.bin
+
+# fmt normalises layout without changing the output, --check fails on unformatted sources
+target/debug/vmw_assembler fmt test/fmt/messy.asm > test/output.asm
+if ! cmp -s test/output.asm test/fmt/messy.formatted.asm; then
+    echo "fmt test/fmt/messy.asm: output differs from test/fmt/messy.formatted.asm"
+    exit 1
+fi
+rm test/output.asm
+target/debug/vmw_assembler assemble test/fmt/messy.asm -o test/output.bin 2> /dev/null
+target/debug/vmw_assembler assemble test/fmt/messy.formatted.asm -o test/formatted.bin 2> /dev/null
+if ! cmp -s test/output.bin test/formatted.bin; then
+    echo "fmt test/fmt/messy.asm: formatted source assembles differently"
+    exit 1
+fi
+rm test/output.bin test/formatted.bin
+if ! target/debug/vmw_assembler fmt --check test/fmt/messy.formatted.asm > /dev/null; then
+    echo "fmt --check: test/fmt/messy.formatted.asm is formatted"
+    exit 1
+fi
+if target/debug/vmw_assembler fmt --check test/fmt/messy.asm > /dev/null; then
+    echo "fmt --check: test/fmt/messy.asm is not formatted"
+    exit 1
+fi
+
+# language server: diagnostics, definition, hover, completion and symbols of a scripted session,
+# a malformed message, labels that are not ascii and a half typed reference do not end it
+sh test/lsp/session.sh | target/debug/vmw_assembler lsp > test/output.txt
+if [ $? -ne 0 ]; then
+    echo "lsp: expected exit code 0 after shutdown and exit"
+    exit 1
+fi
+for expected in '"message":"proc other: could not find label missing"' \
+                '"id":2,"result":{"uri":"[^"]*","range":{"start":{"line":2,"character":0}' \
+                '"id":3,"result":{"contents":{"kind":"markdown","value":"\*\*push_u8\*\*\\n\\nopcode 6' \
+                '"label":"&loop","kind":18' \
+                '"name":"other","kind":12' \
+                '"id":null,"error":{"code":-32700' \
+                '"message":"invalid token: Né:"' \
+                '"message":"invalid token: &console."' \
+                '"id":6,"result":null'; do
+    if ! grep -q "$expected" test/output.txt; then
+        echo "lsp: response is missing $expected"
+        exit 1
+    fi
+done
+rm test/output.txt
+
+# the legacy dialect migrates to the current syntax, and --legacy assembles it to the same bytes
+for f in asm/hello_world.asm asm/console/printcstr.asm asm/console/printc.old.asm; do
+    name=$(basename $f .asm)
+    target/debug/vmw_assembler migrate $f -o test/output.asm
+    if ! cmp -s test/output.asm test/legacy/${name%.old}.migrated.asm; then
+        echo "migrate $f: output differs from test/legacy/${name%.old}.migrated.asm"
+        exit 1
+    fi
+done
+target/debug/vmw_assembler assemble --legacy asm/console/printcstr.asm -o test/output.bin 2> /dev/null
+target/debug/vmw_assembler assemble test/legacy/printcstr.migrated.asm -o test/migrated.bin 2> /dev/null
+if ! cmp -s test/output.bin test/migrated.bin; then
+    echo "assemble --legacy asm/console/printcstr.asm: output differs from the migrated source"
+    exit 1
+fi
+rm test/output.asm test/output.bin test/migrated.bin
+
+# --format flat resolves addresses for the origin set with org and writes no tables
+target/debug/vmw_assembler assemble --format flat test/legacy/printcstr.migrated.asm -o test/output.bin
+if ! cmp -s test/output.bin test/legacy/printcstr.flat.bin; then
+    echo "assemble --format flat test/legacy/printcstr.migrated.asm: output differs from test/legacy/printcstr.flat.bin"
+    exit 1
+fi
+rm test/output.bin
+if target/debug/vmw_assembler assemble --format flat test/link/main.asm -o test/output.bin 2> /dev/null; then
+    echo "assemble --format flat test/link/main.asm: expected an error for the external call"
+    exit 1
+fi
+
+# intel hex and s-records hold the flat code with its base address, the disassembler reads them back
+target/debug/vmw_assembler assemble --format ihex test/legacy/printcstr.migrated.asm -o test/output.hex
+target/debug/vmw_assembler assemble --format srec test/legacy/printcstr.migrated.asm -o test/output.srec
+if ! cmp -s test/output.hex test/image/printcstr.hex || ! cmp -s test/output.srec test/image/printcstr.srec; then
+    echo "assemble --format ihex, srec: output differs from test/image/printcstr.hex, test/image/printcstr.srec"
+    exit 1
+fi
+for format in ihex srec; do
+    target/debug/vmw_assembler disassemble --format $format test/output.${format#i} > test/output.txt
+    if ! cmp -s test/output.txt test/image/printcstr.txt; then
+        echo "disassemble --format $format: output differs from test/image/printcstr.txt"
+        exit 1
+    fi
+done
+rm test/output.hex test/output.srec test/output.txt
+# records far apart are refused instead of filling 4 GiB with zeros
+target/debug/vmw_assembler disassemble --format ihex test/image/far_apart.hex > /dev/null 2>&1
+if [ $? -ne 1 ]; then
+    echo "disassemble --format ihex test/image/far_apart.hex: expected an error for records 4 GiB apart"
+    exit 1
+fi
+# more than 0xFFFF data records are counted by S6 instead of S5
+{ echo "proc start:"; awk 'BEGIN { for (i = 0; i < 105000; i++) print "push_u64 0x0" }'; echo "halt"; echo "end proc"; } > test/output.asm
+target/debug/vmw_assembler assemble --format srec test/output.asm -o test/output.srec
+if ! grep -q "^S60401005AA0" test/output.srec || grep -q "^S5" test/output.srec; then
+    echo "assemble --format srec: expected an S6 count for more than 0xFFFF records"
+    exit 1
+fi
+rm test/output.asm test/output.srec
+
+# json documents hold every part of a module, so converting back gives the same bytes
+for f in test/*.bin; do
+    for encoding in "" --base64; do
+        target/debug/vmw_assembler convert $encoding $f -o test/output.json
+        target/debug/vmw_assembler convert test/output.json -o test/output.bin
+        if ! cmp -s test/output.bin $f; then
+            echo "convert $encoding $f: json does not convert back to the same module"
+            exit 1
+        fi
+    done
+done
+target/debug/vmw_assembler assemble --format json test/link/main.asm -o test/output.json
+if ! cmp -s test/output.json test/json/main.json; then
+    echo "assemble --format json test/link/main.asm: output differs from test/json/main.json"
+    exit 1
+fi
+target/debug/vmw_assembler assemble test/link/console.asm -o test/output.bin 2> /dev/null
+target/debug/vmw_assembler convert test/output.bin -o test/console.json
+target/debug/vmw_assembler run test/output.json test/console.json > test/output.txt
+if ! cmp -s test/output.txt test/link/main.out; then
+    echo "run of json modules: output differs from test/link/main.out"
+    exit 1
+fi
+# arrays nested deeper than the parser allows are an error instead of a stack overflow
+printf '{"vmw":%s%s}' "$(head -c 100000 /dev/zero | tr '\0' '[')" "$(head -c 100000 /dev/zero | tr '\0' ']')" > test/output.json
+if ! target/debug/vmw_assembler convert test/output.json -o /dev/null 2>&1 | grep -q "nested deeper than 128"; then
+    echo "convert of deeply nested json: expected an error"
+    exit 1
+fi
+rm test/output.json test/output.bin test/console.json test/output.txt
+
+# the wasm build assembles with in-memory includes to the same bytes, skipped without the target or node
+if command -v node > /dev/null && cargo build --release --lib --target wasm32-unknown-unknown 2> /dev/null; then
+    target/debug/vmw_assembler assemble -D letter=0x41 test/include/main.asm -o test/output.bin
+    if ! node test/wasm/test.js target/wasm32-unknown-unknown/release/vmw_assembler.wasm test/output.bin; then
+        exit 1
+    fi
+    rm test/output.bin
+else
+    echo "skipping the wasm test, it needs node and the wasm32-unknown-unknown target"
+fi
+
+# modules without signatures keep the original layout, the signature section is read back when there is one
+if ! target/debug/vmw_assembler dump test/printcstr.bin > /dev/null; then
+    echo "dump test/printcstr.bin: module of the original layout does not load"
+    exit 1
+fi
+if ! target/debug/vmw_assembler dump test/signatures.bin | grep -q "printc(u8)->()"; then
+    echo "dump test/signatures.bin: signature of printc is missing"
+    exit 1
+fi
+# a reader of the original layout finds the code of a module with signatures at index[0] + index[3], the signatures only follow it
+start=$((0x$(xxd -p -s 0 -l 8 test/signatures.bin)))
+binary=$((0x$(xxd -p -s 24 -l 8 test/signatures.bin)))
+target/debug/vmw_assembler convert --format flat --base 0 test/signatures.bin -o test/output.bin
+if ! dd if=test/signatures.bin bs=1 skip=$((start + binary)) count=$(wc -c < test/output.bin) 2> /dev/null | cmp -s - test/output.bin; then
+    echo "test/signatures.bin: code is not where the original layout puts it"
+    exit 1
+fi
+rm test/output.bin
@@ -0,0 +1,27 @@
+proc start:
+push_u8 0x7
+push_u8 0x3
+add_u8
+push_u8 0x2
+sub_u8
+push_u8 0x3
+mul_u8
+push_u8 0x4
+div_u8
+push_u8 0x3
+mod_u8
+pop_u8
+push_u64 0x10000
+push_u64 0x3
+add_u64
+push_u64 0x2
+sub_u64
+push_u64 0x3
+mul_u64
+push_u64 0x4
+div_u64
+push_u64 0x3
+mod_u64
+spd 0x8
+halt
+end proc
\ No newline at end of file
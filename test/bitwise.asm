@@ -0,0 +1,29 @@
+proc start:
+push_u8 0xF0
+push_u8 0x3C
+and_u8
+push_u8 0x01
+or_u8
+push_u8 0xFF
+xor_u8
+not_u8
+push_u8 0x2
+shl_u8
+push_u8 0x1
+shr_u8
+pop_u8
+push_u64 0xFF00FF00
+push_u64 0xFFFF
+and_u64
+push_u64 0x1
+or_u64
+push_u64 0xFF
+xor_u64
+not_u64
+push_u8 0x8
+shl_u64
+push_u8 0x4
+shr_u64
+spd 0x8
+halt
+end proc
\ No newline at end of file
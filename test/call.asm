@@ -0,0 +1,19 @@
+proc start:
+cpl_u8 0x1
+push_u8 0x0
+cmp_u8
+jmp_true &finish
+cpl_u8 0x1
+call &console.printc
+pop_u8
+jmp &this.start
+finish:
+pop_u8
+push_u8 0xA
+call &console.printc
+ret
+end proc
+
+proc tail:
+call &this.start
+end proc
\ No newline at end of file
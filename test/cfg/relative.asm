@@ -0,0 +1,18 @@
+proc start:
+push_u8 0x1
+jmp_rel8 0x3
+push_u8 0x2
+halt
+end proc
+
+proc other:
+push_u8 0x0
+cpl_u8 0x0
+jmp_true &done
+pop_u8
+jmp_rel8 0xE7
+done:
+pop_u8
+pop_u8
+halt
+end proc
\ No newline at end of file
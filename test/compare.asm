@@ -0,0 +1,94 @@
+proc start:
+push_u8 0x0
+loop:
+cpl_u8 0x0
+push_u8 0xA
+cmp_lt_u8
+jmp_false &done
+push_u8 0x1
+add_u8
+jmp &loop
+done:
+pop_u8
+jmp &this.all
+end proc
+
+proc all:
+push_u8 0x1
+push_u8 0xFF
+cmp_ne_u8
+pop_u8
+push_u8 0x1
+push_u8 0xFF
+cmp_lt_u8
+pop_u8
+push_u8 0x1
+push_u8 0xFF
+cmp_lt_i8
+pop_u8
+push_u8 0x1
+push_u8 0xFF
+cmp_le_u8
+pop_u8
+push_u8 0x1
+push_u8 0xFF
+cmp_le_i8
+pop_u8
+push_u8 0x1
+push_u8 0xFF
+cmp_gt_u8
+pop_u8
+push_u8 0x1
+push_u8 0xFF
+cmp_gt_i8
+pop_u8
+push_u8 0x1
+push_u8 0xFF
+cmp_ge_u8
+pop_u8
+push_u8 0x1
+push_u8 0xFF
+cmp_ge_i8
+pop_u8
+push_u64 0x1
+push_u64 0xFFFFFFFFFFFFFFFF
+cmp_u64
+pop_u8
+push_u64 0x1
+push_u64 0xFFFFFFFFFFFFFFFF
+cmp_ne_u64
+pop_u8
+push_u64 0x1
+push_u64 0xFFFFFFFFFFFFFFFF
+cmp_lt_u64
+pop_u8
+push_u64 0x1
+push_u64 0xFFFFFFFFFFFFFFFF
+cmp_lt_i64
+pop_u8
+push_u64 0x1
+push_u64 0xFFFFFFFFFFFFFFFF
+cmp_le_u64
+pop_u8
+push_u64 0x1
+push_u64 0xFFFFFFFFFFFFFFFF
+cmp_le_i64
+pop_u8
+push_u64 0x1
+push_u64 0xFFFFFFFFFFFFFFFF
+cmp_gt_u64
+pop_u8
+push_u64 0x1
+push_u64 0xFFFFFFFFFFFFFFFF
+cmp_gt_i64
+pop_u8
+push_u64 0x1
+push_u64 0xFFFFFFFFFFFFFFFF
+cmp_ge_u64
+pop_u8
+push_u64 0x1
+push_u64 0xFFFFFFFFFFFFFFFF
+cmp_ge_i64
+pop_u8
+halt
+end proc
\ No newline at end of file
@@ -0,0 +1,5 @@
+proc start:
+loop:
+cpl_u8 &loop
+jmp &loop
+end proc
\ No newline at end of file
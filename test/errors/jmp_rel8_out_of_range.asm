@@ -0,0 +1,105 @@
+proc start:
+loop:
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+halt
+jmp_rel8 &loop
+end proc
\ No newline at end of file
@@ -0,0 +1,5 @@
+proc start:
+    halt
+end proc
+
+org 0x1000
\ No newline at end of file
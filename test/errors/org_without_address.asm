@@ -0,0 +1,5 @@
+org
+
+proc start:
+    halt
+end proc
\ No newline at end of file
@@ -0,0 +1,4 @@
+proc start:
+push_u16 0x10000
+halt
+end proc
\ No newline at end of file
@@ -0,0 +1,4 @@
+proc start:
+push_u8 0x100
+halt
+end proc
\ No newline at end of file
@@ -0,0 +1,4 @@
+proc start:
+spd 0x8000000000000000
+halt
+end proc
\ No newline at end of file
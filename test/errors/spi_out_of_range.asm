@@ -0,0 +1,5 @@
+proc start:
+spi 0x7FFFFFFFFFFFFFFF
+spi 0x7FFFFFFFFFFFFFFF
+halt
+end proc
\ No newline at end of file
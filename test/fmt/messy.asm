@@ -0,0 +1,24 @@
+include "../link/console.asm"
+
+
+; prints a character twice
+proc   print(u8)->( )   ; with a signature
+
+	CPL_U8 0x09
+  call &this.printc
+
+
+
+    cpl_u8 10 ; below the first copy
+  loop:   CALL &this.printc pop_u8 pop_u8
+ RET
+end proc
+proc start:
+
+  push_u8 0x0a
+	call &this.print
+   pop_u8
+        halt
+
+
+end proc
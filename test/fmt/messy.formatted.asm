@@ -0,0 +1,21 @@
+include "../link/console.asm"
+
+; prints a character twice
+proc print(u8) -> () ; with a signature
+    cpl_u8 0x9
+    call   &this.printc
+
+    cpl_u8 10 ; below the first copy
+loop:
+    call   &this.printc
+    pop_u8
+    pop_u8
+    ret
+end proc
+
+proc start:
+    push_u8 0xA
+    call    &this.print
+    pop_u8
+    halt
+end proc
@@ -0,0 +1 @@
+include "../cycle/a.asm"
\ No newline at end of file
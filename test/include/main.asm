@@ -0,0 +1,11 @@
+include "printc.asm"
+
+proc start:
+push_u8 letter
+call &this.printc
+pop_u8
+push_u8 0xA
+call &this.printc
+pop_u8
+halt
+end proc
\ No newline at end of file
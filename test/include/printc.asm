@@ -0,0 +1,7 @@
+proc printc(u8) -> ()
+cpl_u8 0x9
+set_u8 0xBB8
+push_u8 0x1
+set_u8 0xBB9
+ret
+end proc
\ No newline at end of file
@@ -0,0 +1,32 @@
+proc helloworld:
+    push_u64 &end
+    push_u8  0x0
+    push_u8  0x21
+    push_u8  0x64
+    push_u8  0x6C
+    push_u8  0x72
+    push_u8  0x6F
+    push_u8  0x57
+    push_u8  0x20
+    push_u8  0x6F
+    push_u8  0x6C
+    push_u8  0x6C
+    push_u8  0x65
+    push_u8  0x48
+    push_u8  0x35
+    jmp      &printcstr
+end:
+    halt
+
+printcstr:
+    cpl_u8   0x1
+    push_u8  0x0
+    cmp_u8
+    jmp_true &return
+    jmp      &printchar
+    pop_u8
+    push_u8  0xA
+    jmp      &printchar
+return:
+    jmps
+end proc
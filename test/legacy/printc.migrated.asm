@@ -0,0 +1,8 @@
+proc printc:
+    set_u8   0xBB8
+    push_u8  0x1
+    set_u8   0xBB9
+    cpg_u8   0xBB9
+    jmp_true 0x7E7
+    jmps
+end proc
@@ -0,0 +1,18 @@
+org 0x834
+
+proc start:
+    cpl_u8   0x1
+    push_u8  0x0
+    cmp_u8
+    jmp_true &finish
+    push_u64 &poploop
+    cpl_u8   0x9
+    jmp      0x7D0
+poploop:
+    pop_u8
+    jmp      &this.start
+finish:
+    pop_u8
+    push_u8  0xA
+    jmp      0x7D0
+end proc
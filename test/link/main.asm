@@ -0,0 +1,12 @@
+proc start:
+push_u8 0x48
+call &console.printc
+pop_u8
+push_u8 0x69
+call &console.printc
+pop_u8
+push_u8 0xA
+call &console.printc
+pop_u8
+halt
+end proc
\ No newline at end of file
@@ -0,0 +1,20 @@
+# writes a language server session to stdout, every message framed with its content length
+message() {
+    printf 'Content-Length: %d\r\n\r\n%s' $(printf '%s' "$1" | wc -c) "$1"
+}
+uri="file://$(pwd)/test/lsp/document.asm"
+text='proc start:\npush_u8 0x1\nloop:\njmp &loop\nend proc\n\nproc other:\njmp &missing\nend proc'
+message '{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"capabilities":{}}}'
+message '{"jsonrpc":"2.0","method":"initialized","params":{}}'
+message '{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"'$uri'","languageId":"vmw","version":1,"text":"'"$text"'"}}}'
+message '{"jsonrpc":"2.0","id":2,"method":"textDocument/definition","params":{"textDocument":{"uri":"'$uri'"},"position":{"line":3,"character":6}}}'
+message '{"jsonrpc":"2.0","id":3,"method":"textDocument/hover","params":{"textDocument":{"uri":"'$uri'"},"position":{"line":1,"character":2}}}'
+message '{"jsonrpc":"2.0","id":4,"method":"textDocument/completion","params":{"textDocument":{"uri":"'$uri'"},"position":{"line":3,"character":0}}}'
+message '{"jsonrpc":"2.0","id":5,"method":"textDocument/documentSymbol","params":{"textDocument":{"uri":"'$uri'"}}}'
+message '{bad}'
+other="file://$(pwd)/test/lsp/other.asm"
+message '{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"'$other'","languageId":"vmw","version":1,"text":"proc start:\nné:\nNé:\njmp &né\nend proc"}}}'
+half="file://$(pwd)/test/lsp/half.asm"
+message '{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"'$half'","languageId":"vmw","version":1,"text":"proc start:\ncall &console.\nend proc"}}}'
+message '{"jsonrpc":"2.0","id":6,"method":"shutdown"}'
+message '{"jsonrpc":"2.0","method":"exit"}'
@@ -0,0 +1,37 @@
+proc start:
+push_u8 0x1
+pop_u8
+spi 0x8
+spi 0x8
+spd 0x10
+spi 0x4
+spd 0x1
+jmp &next
+next:
+jmp &first
+push_u8 0x2
+halt
+first:
+push_u8 0x0
+push_u8 0x0
+cmp_u8
+jmp_true &hop
+pop_u8
+push_u64 &back
+jmp &this.other
+hop:
+pop_u8
+jmp &tail
+back:
+spd 0x3
+halt
+tail:
+jmp &back
+end proc
+
+proc other:
+loopa:
+jmp &loopb
+loopb:
+jmp &loopa
+end proc
\ No newline at end of file
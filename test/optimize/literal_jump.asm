@@ -0,0 +1,8 @@
+proc start:
+push_u8 0x1
+jmp_rel8 0x5
+push_u8 0x2
+pop_u8
+pop_u8
+halt
+end proc
\ No newline at end of file
@@ -0,0 +1,9 @@
+proc start:
+halt
+unused:
+spi 0x7FFFFFFFFFFFFFFF
+spi 0x7FFFFFFFFFFFFFFF
+spd 0x8000000000000000
+spd 0x8000000000000000
+jmp &unused
+end proc
\ No newline at end of file
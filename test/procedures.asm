@@ -0,0 +1,22 @@
+proc start:
+push_u64 &done
+jmp &this.first
+done:
+halt
+end proc
+
+proc first:
+jmp &this.second
+end proc
+
+proc second:
+jmp &this.third
+end proc
+
+proc third:
+jmp &this.fourth
+end proc
+
+proc fourth:
+jmps
+end proc
\ No newline at end of file
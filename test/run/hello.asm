@@ -0,0 +1,15 @@
+proc start:
+push_u8 0x48
+set_u8 0xBB8
+push_u8 0x1
+set_u8 0xBB9
+push_u8 0x69
+set_u8 0xBB8
+push_u8 0x1
+set_u8 0xBB9
+push_u8 0xA
+set_u8 0xBB8
+push_u8 0x1
+set_u8 0xBB9
+halt
+end proc
\ No newline at end of file
@@ -0,0 +1,88 @@
+proc start:
+push_u8 0x0
+jmp &loop
+again:
+pop_u8
+loop:
+push_u8 0x1
+add_u8
+cpl_u8 0x0
+push_u8 0xFF
+cmp_u8
+jmp_true &far
+pop_u8
+jmp &loop
+far:
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x1
+pop_u8
+push_u8 0x0
+jmp_false &again
+pop_u8
+jmp_rel8 &end
+end:
+pop_u8
+halt
+end proc
+
+proc next:
+jmp &this.start
+end proc
\ No newline at end of file
@@ -0,0 +1,18 @@
+proc start:
+push_u8 0x48
+call &this.printc
+pop_u8
+push_u8 0x1
+push_u64 0x2
+jmp &this.sum
+end proc
+
+proc printc(u8) -> ()
+cpl_u8 0x9
+set_u8 0xBB8
+ret
+end proc
+
+proc sum(u8, u64) -> (u64)
+halt
+end proc
\ No newline at end of file
@@ -0,0 +1,27 @@
+proc start:
+push_u8 0x3
+loop:
+cpl_u8 0x0
+call &this.printc
+pop_u8
+push_u8 0x1
+sub_u8
+cpl_u8 0x0
+push_u8 0x0
+cmp_ne_u8
+jmp_false &done
+pop_u8
+jmp &loop
+done:
+pop_u8
+pop_u8
+halt
+end proc
+
+proc printc(u8) -> ()
+cpl_u8 0x9
+set_u8 0xBB8
+push_u8 0x1
+set_u8 0xBB9
+ret
+end proc
\ No newline at end of file
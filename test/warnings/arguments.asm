@@ -0,0 +1,10 @@
+proc start:
+push_u64 0x48
+call &this.printc
+halt
+end proc
+
+proc printc(u8) -> ()
+set_u8 0xBB8
+ret
+end proc
\ No newline at end of file
@@ -0,0 +1,4 @@
+proc start:
+push_u8 0x1
+pop_u8
+end proc
\ No newline at end of file
@@ -0,0 +1,6 @@
+proc start:
+loop:
+push_u8 0x1
+pop_u8
+jmp &loop
+end proc
\ No newline at end of file
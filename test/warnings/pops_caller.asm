@@ -0,0 +1,12 @@
+proc start:
+push_u64 &back
+push_u8 0x48
+jmp &this.printc
+back:
+halt
+end proc
+
+proc printc:
+set_u8 0xBB8
+jmps
+end proc
\ No newline at end of file
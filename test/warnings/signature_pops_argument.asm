@@ -0,0 +1,11 @@
+proc start:
+push_u8 0x48
+call &this.printc
+pop_u8
+halt
+end proc
+
+proc printc(u8) -> ()
+set_u8 0xBB8
+ret
+end proc
\ No newline at end of file
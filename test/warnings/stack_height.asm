@@ -0,0 +1,15 @@
+proc start:
+cpl_u8 0x1
+push_u8 0x0
+cmp_u8
+jmp_true &finish
+push_u64 &poploop
+cpl_u8 0x9
+jmp &console.printc
+poploop:
+jmp &this.start
+finish:
+pop_u8
+push_u8 0xA
+jmp &console.printc
+end proc
\ No newline at end of file
@@ -0,0 +1,4 @@
+proc start:
+unused:
+halt
+end proc
\ No newline at end of file
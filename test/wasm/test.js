@@ -0,0 +1,33 @@
+// assembles test/include/main.asm with the wasm build and in-memory includes,
+// the bytes must match the native build, argv: wasm file, native output
+var fs = require("fs");
+var VmwAssembler = require("../../web/vmw_assembler.js");
+
+VmwAssembler.load(fs.readFileSync(process.argv[2])).then(function (assembler) {
+    var result = assembler.assemble(fs.readFileSync("test/include/main.asm", "utf8"), {
+        path: "test/include/main.asm",
+        files: {"test/include/printc.asm": fs.readFileSync("test/include/printc.asm", "utf8")},
+        defines: {letter: "0x41"}
+    });
+    if (!result.ok || Buffer.compare(Buffer.from(result.bytes), fs.readFileSync(process.argv[3])) != 0) {
+        console.log("wasm: test/include/main.asm assembles differently than the native build");
+        process.exit(1);
+    }
+    if (!result.map.some(function (symbol) { return symbol.name == "printc" && symbol.kind == "proc"; })) {
+        console.log("wasm: map is missing proc printc");
+        process.exit(1);
+    }
+
+    var failed = assembler.assemble("include \"missing.asm\"\n");
+    if (failed.ok || failed.bytes !== null || failed.diagnostics[0].message != "main.asm: could not find include missing.asm") {
+        console.log("wasm: expected an error for a missing include, got " + JSON.stringify(failed));
+        process.exit(1);
+    }
+
+    // the same file under another spelling is still a cycle, not a recursion without end
+    var cycle = assembler.assemble("include \"lib/a.asm\"\n", {files: {"lib/a.asm": "include \"../lib/a.asm\"\n"}});
+    if (cycle.ok || cycle.diagnostics[0].message != "lib/../lib/a.asm: includes itself") {
+        console.log("wasm: expected an include cycle, got " + JSON.stringify(cycle));
+        process.exit(1);
+    }
+});
\ No newline at end of file
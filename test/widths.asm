@@ -0,0 +1,27 @@
+proc start:
+push_u8 0x12
+push_u16 0x1234
+push_u32 0x12345678
+push_u64 0x123456789ABCDEF0
+set_u64 0x1000
+set_u32 0x1008
+set_u16 0x100C
+set_u8 0x100E
+cpg_u8 0x100E
+cpg_u16 0x100C
+cpg_u32 0x1008
+cpg_u64 0x1000
+cpl_u8 0xE
+cpl_u16 0xC
+cpl_u32 0x8
+cpl_u64 0x0
+pop_u64
+pop_u32
+pop_u16
+pop_u8
+pop_u64
+pop_u32
+pop_u16
+pop_u8
+halt
+end proc
\ No newline at end of file
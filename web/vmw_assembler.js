@@ -0,0 +1,58 @@
+// wrapper around the wasm build of the assembler, for browsers and node
+//
+//     cargo build --release --lib --target wasm32-unknown-unknown
+//     const assembler = await VmwAssembler.load(bytes of target/wasm32-unknown-unknown/release/vmw_assembler.wasm)
+//     const result = assembler.assemble(source, {files: {"printc.asm": text}, defines: {letter: "0x41"}})
+//
+// result is {ok, bytes: Uint8Array or null, origin, diagnostics: [{severity, message}], map}, see src/web.rs
+var VmwAssembler = (function () {
+    function decodeBase64(text) {
+        if (typeof Buffer !== "undefined") {
+            return new Uint8Array(Buffer.from(text, "base64"));
+        }
+        var binary = atob(text);
+        var bytes = new Uint8Array(binary.length);
+        for (var i = 0; i < binary.length; i++) {
+            bytes[i] = binary.charCodeAt(i);
+        }
+        return bytes;
+    }
+
+    function Assembler(instance) {
+        this.exports = instance.exports;
+    }
+
+    // options are the members of the request besides source, like path, files, defines and optimize
+    Assembler.prototype.assemble = function (source, options) {
+        var request = Object.assign({}, options || {}, {source: source});
+        var input = new TextEncoder().encode(JSON.stringify(request));
+        var exports = this.exports;
+        var requestPointer = exports.vmw_alloc(input.length);
+        new Uint8Array(exports.memory.buffer, requestPointer, input.length).set(input);
+        var responsePointer = exports.vmw_assemble(requestPointer, input.length);
+        exports.vmw_free(requestPointer, input.length);
+        // memory may have grown, so views are made after the call
+        var length = new DataView(exports.memory.buffer).getUint32(responsePointer, true);
+        var text = new TextDecoder().decode(new Uint8Array(exports.memory.buffer, responsePointer + 4, length));
+        exports.vmw_free(responsePointer, length + 4);
+
+        var response = JSON.parse(text);
+        if (response.bytes !== null) {
+            response.bytes = decodeBase64(response.bytes);
+        }
+        return response;
+    };
+
+    // wasm is the bytes of the module or a compiled WebAssembly.Module
+    function load(wasm) {
+        return WebAssembly.instantiate(wasm, {}).then(function (result) {
+            return new Assembler(result.instance || result);
+        });
+    }
+
+    return {load: load};
+})();
+
+if (typeof module !== "undefined") {
+    module.exports = VmwAssembler;
+}
\ No newline at end of file